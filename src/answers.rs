@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+
+use crate::{
+    backend::{
+        answer_confirm, answer_multi_select, answer_select, answer_text, fallback, Item,
+        PromptBackend, Request, Response,
+    },
+    error::Result,
+    parser::Value,
+};
+
+/// Pre-recorded answers used instead of interactive prompts, keyed by
+/// state key or variable name.
+#[derive(Debug, Clone, Default)]
+pub struct Answers {
+    values: HashMap<String, Value>,
+    use_defaults: bool,
+}
+
+impl Answers {
+    pub fn new(values: HashMap<String, Value>) -> Self {
+        Self {
+            values,
+            use_defaults: false,
+        }
+    }
+
+    /// Answers prompts that have no recorded answer with their default
+    /// instead of failing.
+    pub fn use_defaults(mut self, use_defaults: bool) -> Self {
+        self.use_defaults = use_defaults;
+        self
+    }
+
+    /// Looks the answer up by state key first and then by variable name.
+    pub fn get(&self, key: &str, name: &str) -> Option<&Value> {
+        self.values.get(key).or_else(|| self.values.get(name))
+    }
+
+    /// Answers from the recorded value, or with `default` when there is
+    /// none for the prompt and defaults are in use.
+    fn respond<T>(
+        &self,
+        request: &Request,
+        default: Option<T>,
+        convert: impl FnOnce(&Value) -> Result<T>,
+    ) -> Result<Response<T>> {
+        // A recorded answer never changes, so asking again after it was
+        // rejected fails instead of looping.
+        match self.get(request.key, request.name).filter(|_| request.error.is_none()) {
+            Some(answer) => Ok(Response::Submit(convert(answer)?)),
+            None => fallback(request, default.filter(|_| self.use_defaults)),
+        }
+    }
+}
+
+impl PromptBackend for Answers {
+    fn text(&mut self, request: &Request, default: Option<&str>) -> Result<Response<String>> {
+        self.respond(request, default.map(str::to_string), |answer| {
+            answer_text(request, answer)
+        })
+    }
+
+    fn password(&mut self, request: &Request, default: Option<&str>) -> Result<Response<String>> {
+        self.text(request, default)
+    }
+
+    fn confirm(&mut self, request: &Request, default: Option<bool>) -> Result<Response<bool>> {
+        self.respond(request, default, |answer| answer_confirm(request, answer))
+    }
+
+    fn select(&mut self, request: &Request, options: &[Item], default: Option<usize>) -> Result<Response<usize>> {
+        self.respond(request, default, |answer| answer_select(request, answer, options))
+    }
+
+    fn multi_select(&mut self, request: &Request, options: &[Item], default: Option<&[usize]>) -> Result<Response<Vec<usize>>> {
+        self.respond(request, default.map(<[usize]>::to_vec), |answer| {
+            answer_multi_select(request, answer, options)
+        })
+    }
+
+    fn fuzzy_select(&mut self, request: &Request, options: &[Item], default: Option<usize>) -> Result<Response<usize>> {
+        self.select(request, options, default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Error;
+
+    use super::*;
+
+    fn answers(values: Vec<(&str, Value)>) -> Answers {
+        Answers::new(
+            values
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+        )
+    }
+
+    fn request<'a>(key: &'a str, name: &'a str) -> Request<'a> {
+        Request {
+            key,
+            name,
+            message: "",
+            error: None,
+        }
+    }
+
+    fn options(values: &[&str]) -> Vec<Item> {
+        values.iter().map(|v| Item::new(*v)).collect()
+    }
+
+    #[test]
+    fn test_answer_by_key_and_name() {
+        let mut answers = answers(vec![
+            ("ask_name", Value::String("John".to_string())),
+            ("ready", Value::Boolean(true)),
+        ]);
+
+        assert_eq!(
+            answers.text(&request("ask_name", "name"), None),
+            Ok(Response::Submit("John".to_string()))
+        );
+        assert_eq!(
+            answers.confirm(&request("ask_ready", "ready"), None),
+            Ok(Response::Submit(true))
+        );
+    }
+
+    #[test]
+    fn test_answer_missing() {
+        assert_eq!(
+            Answers::default().text(&request("ask_name", "name"), None),
+            Err(Error::MissingAnswer(
+                "state 'ask_name' (variable 'name')".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_answer_default() {
+        assert_eq!(
+            Answers::default().text(&request("ask_name", "name"), Some("Jane")),
+            Err(Error::MissingAnswer(
+                "state 'ask_name' (variable 'name')".to_string()
+            ))
+        );
+
+        let mut answers = answers(vec![("ask_name", Value::String("John".to_string()))])
+            .use_defaults(true);
+
+        assert_eq!(
+            answers.text(&request("ask_name", "name"), Some("Jane")),
+            Ok(Response::Submit("John".to_string()))
+        );
+        assert_eq!(
+            answers.confirm(&request("ask_ready", "ready"), Some(false)),
+            Ok(Response::Submit(false))
+        );
+        assert_eq!(
+            answers.multi_select(
+                &request("features", "features"),
+                &options(&["logging", "metrics"]),
+                Some(&[1]),
+            ),
+            Ok(Response::Submit(vec![1]))
+        );
+    }
+
+    #[test]
+    fn test_answer_invalid() {
+        let result = answers(vec![("ready", Value::String("yes".to_string()))])
+            .confirm(&request("ready", "ready"), None);
+        assert!(matches!(result, Err(Error::InvalidAnswer(_))));
+
+        let result = answers(vec![("method", Value::String("put".to_string()))])
+            .select(&request("method", "method"), &options(&["get", "post"]), None);
+        assert_eq!(
+            result,
+            Err(Error::InvalidAnswer(
+                "state 'method': 'put' is not one of the options [get, post]".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_answer_multi_select() {
+        let features = options(&["logging", "metrics", "backup"]);
+
+        let result = answers(vec![(
+            "features",
+            Value::Array(vec![
+                Value::String("logging".to_string()),
+                Value::String("backup".to_string()),
+            ]),
+        )])
+        .multi_select(&request("features", "features"), &features, None);
+        assert_eq!(result, Ok(Response::Submit(vec![0, 2])));
+
+        let result = answers(vec![(
+            "features",
+            Value::Array(vec![Value::String("tracing".to_string())]),
+        )])
+        .multi_select(&request("features", "features"), &features, None);
+        assert!(matches!(result, Err(Error::InvalidAnswer(_))));
+    }
+}
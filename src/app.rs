@@ -1,7 +1,20 @@
-use std::{collections::HashMap, io::Read};
+use std::{collections::HashMap, io::Read, path::Path, process::ExitCode};
 
 use atty::Stream;
-use promptoml::{eval::Context, parser::Value, state::StateMachine, toml::load};
+use promptoml::{
+    answers::Answers,
+    backend::PromkitBackend,
+    check::{check, Severity},
+    eval::Context,
+    key::KeyBinding,
+    graph,
+    output,
+    parser::Value,
+    scenario::{parse_scenarios, run_scenario},
+    session::Session,
+    state::StateMachine,
+    toml::{load_from, to_value},
+};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -12,21 +25,100 @@ pub enum AppError {
     Toml(#[from] promptoml::error::Error),
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
+    #[error("Answer file error: {0}")]
+    Answers(String),
 }
 
 #[derive(Debug, clap::Parser)]
+#[clap(subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
 pub struct App {
-    #[clap(short, long)]
-    config: String,
+    #[clap(short, long, required = true)]
+    config: Option<String>,
     args: Option<String>,
+    /// Key that returns to the previous prompt
+    #[clap(long, default_value = "esc")]
+    back_key: KeyBinding,
+    /// Read prompt answers from a JSON or TOML file instead of the terminal
+    #[clap(long)]
+    answers: Option<String>,
+    /// Answer prompts missing from the answers file with their default
+    #[clap(long, requires = "answers")]
+    use_defaults: bool,
+    /// Save progress to this file after every step so the run can be resumed
+    #[clap(long)]
+    session: Option<String>,
+    /// Continue the run saved in the session file
+    #[clap(long, requires = "session")]
+    resume: bool,
+    /// json, pretty, yaml, toml, dotenv or shell
+    #[clap(long, default_value = "json")]
+    output_format: output::Format,
+    /// Joins the keys of nested values in dotenv and shell output
+    #[clap(long, default_value = "_")]
+    separator: output::Separator,
+    /// Write the answers to this file instead of stdout
+    #[clap(short, long)]
+    output: Option<String>,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum Command {
+    /// Validate a wizard file without running it
+    Check {
+        #[clap(short, long)]
+        config: String,
+    },
+    /// Run scenario files against a wizard without a terminal
+    Test {
+        #[clap(short, long)]
+        config: String,
+        #[clap(required = true)]
+        scenarios: Vec<String>,
+    },
+    /// Print the state graph as a Graphviz DOT or Mermaid flowchart
+    Graph {
+        #[clap(short, long)]
+        config: String,
+        /// dot or mermaid
+        #[clap(short, long, default_value = "dot")]
+        format: graph::Format,
+    },
 }
 
 impl App {
-    pub fn run(&self) -> Result<HashMap<String, serde_json::Value>, AppError> {
-        let config = std::fs::read_to_string(&self.config).map_err(AppError::Io)?;
-        let (start, nodes) = load(&config).map_err(AppError::Toml)?;
+    pub fn run(&self) -> Result<ExitCode, AppError> {
+        match &self.command {
+            Some(Command::Check { config }) => check_config(config),
+            Some(Command::Test { config, scenarios }) => test_config(config, scenarios),
+            Some(Command::Graph { config, format }) => graph_config(config, *format),
+            None => {
+                let config = self.config.as_deref().unwrap_or_default();
+                let context = self.run_wizard(config)?;
+                let content = output::render(context, self.output_format, &self.separator)?;
+                match &self.output {
+                    Some(path) => std::fs::write(path, content)?,
+                    None => print!("{}", content),
+                }
+                Ok(ExitCode::SUCCESS)
+            }
+        }
+    }
+
+    fn run_wizard(&self, config: &str) -> Result<HashMap<String, Value>, AppError> {
+        let content = std::fs::read_to_string(config).map_err(AppError::Io)?;
+        let (start, nodes) = load_from(&content, Path::new(config)).map_err(AppError::Toml)?;
+
+        let session = self.session.as_ref().map(|path| Session::new(path, &content));
+        let checkpoint = match &session {
+            Some(session) if self.resume => Some(session.load()?),
+            _ => None,
+        };
 
-        let args = if let Some(args) = &self.args {
+        let args = if checkpoint.is_some() {
+            "{}".to_string()
+        } else if let Some(args) = &self.args {
             args.clone()
         } else if atty::is(Stream::Stdin) {
             "{}".to_string()
@@ -43,49 +135,106 @@ impl App {
         let args = to_state_value(args);
 
         let context = Context::new(args);
-        let mut state = StateMachine::new(nodes, context);
+        let mut state = StateMachine::new(nodes, context).with_wizard(Path::new(config));
+        state = match &self.answers {
+            Some(answers) => state.with_backend(load_answers(answers)?.use_defaults(self.use_defaults)),
+            None => state.with_backend(PromkitBackend::new(self.back_key)),
+        };
+        if let Some(session) = session {
+            state = state.with_session(session);
+        }
 
-        state.run(start)?;
+        match checkpoint {
+            Some(checkpoint) => state.resume(checkpoint)?,
+            None => state.run(start)?,
+        }
 
         let mut context = state.context.get_context().clone();
         context.remove("args");
-
-        Ok(context
-            .into_iter()
-            .map(|(k, v)| (k, to_serde_json(v)))
-            .collect())
+        Ok(context)
     }
 }
 
-fn to_state_value(value: HashMap<String, serde_json::Value>) -> HashMap<String, Value> {
-        value
-        .into_iter()
-        .map(|(k, v)| (k, inner_to_state_value(v)))
-        .collect()
-}
+fn check_config(config: &str) -> Result<ExitCode, AppError> {
+    let content = std::fs::read_to_string(config).map_err(AppError::Io)?;
+    let (start, nodes) = load_from(&content, Path::new(config)).map_err(AppError::Toml)?;
 
-fn inner_to_state_value(value: serde_json::Value) -> Value {
-    match value {
-        serde_json::Value::String(s) => Value::String(s),
-        serde_json::Value::Number(n) => Value::Number(n.as_f64().unwrap()),
-        serde_json::Value::Bool(b) => Value::Boolean(b),
-        serde_json::Value::Object(o) => Value::Object(to_state_value(o.into_iter().collect())),
-        serde_json::Value::Array(a) => Value::Array(a.into_iter().map(inner_to_state_value).collect()),
-        _ => Value::String(value.to_string()),
+    let diagnostics = check(&start, &nodes);
+    for diagnostic in &diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+
+    let errors = diagnostics
+        .iter()
+        .filter(|d| d.severity() == Severity::Error)
+        .count();
+    if errors > 0 {
+        eprintln!("{}: {} error(s) found", config, errors);
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
     }
 }
 
-fn to_serde_json(value: Value) -> serde_json::Value {
-    match value {
-        Value::String(s) => serde_json::Value::String(s),
-        Value::Number(n) => serde_json::Value::Number(serde_json::Number::from_f64(n).unwrap()),
-        Value::Boolean(b) => serde_json::Value::Bool(b),
-        Value::Symbol(s) => serde_json::Value::String(s),
-        Value::Object(o) => {
-            serde_json::Value::Object(o.into_iter().map(|(k, v)| (k, to_serde_json(v))).collect())
-        }
-        Value::Array(a) => {
-            serde_json::Value::Array(a.into_iter().map(|v| to_serde_json(v)).collect())
+fn graph_config(config: &str, format: graph::Format) -> Result<ExitCode, AppError> {
+    let content = std::fs::read_to_string(config).map_err(AppError::Io)?;
+    let (start, nodes) = load_from(&content, Path::new(config)).map_err(AppError::Toml)?;
+
+    print!("{}", graph::render(&start, &nodes, format));
+    Ok(ExitCode::SUCCESS)
+}
+
+fn test_config(config: &str, files: &[String]) -> Result<ExitCode, AppError> {
+    let content = std::fs::read_to_string(config).map_err(AppError::Io)?;
+    let (start, nodes) = load_from(&content, Path::new(config)).map_err(AppError::Toml)?;
+
+    let (mut passed, mut failed) = (0, 0);
+    for file in files {
+        let content = std::fs::read_to_string(file).map_err(AppError::Io)?;
+        for scenario in parse_scenarios(&content).map_err(AppError::Toml)? {
+            let outcome = run_scenario(&start, &nodes, &scenario);
+            if outcome.passed() {
+                passed += 1;
+                println!("PASS {} :: {}", file, outcome.name);
+            } else {
+                failed += 1;
+                println!("FAIL {} :: {}", file, outcome.name);
+                for failure in &outcome.failures {
+                    for line in failure.to_string().lines() {
+                        println!("  {}", line);
+                    }
+                }
+            }
         }
     }
+
+    println!("{} passed, {} failed", passed, failed);
+    if failed > 0 {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn load_answers(path: &str) -> Result<Answers, AppError> {
+    let content = std::fs::read_to_string(path).map_err(AppError::Io)?;
+
+    let values = if path.ends_with(".toml") {
+        let table: toml::Table =
+            toml::from_str(&content).map_err(|e| AppError::Answers(e.to_string()))?;
+        table
+            .into_iter()
+            .map(|(k, v)| (k, to_value(v)))
+            .collect()
+    } else {
+        let values: HashMap<String, serde_json::Value> =
+            serde_json::from_str(&content).map_err(AppError::Json)?;
+        to_state_value(values)
+    };
+
+    Ok(Answers::new(values))
+}
+
+fn to_state_value(value: HashMap<String, serde_json::Value>) -> HashMap<String, Value> {
+    value.into_iter().map(|(k, v)| (k, Value::from(v))).collect()
 }
@@ -0,0 +1,603 @@
+use std::collections::{HashMap, VecDeque};
+
+use promkit::{
+    crossterm::event::Event,
+    pane::Pane,
+    preset::{
+        checkbox::Checkbox,
+        listbox::Listbox,
+        query_selector::QuerySelector,
+        readline::{self, Readline},
+    },
+    Finalizer, PromptSignal, Renderer,
+};
+
+use crate::{
+    error::{Error, Result},
+    key::KeyBinding,
+    parser::Value,
+    template::to_text,
+};
+
+/// The prompt state that is asking for input.
+#[derive(Debug, Clone, Copy)]
+pub struct Request<'a> {
+    /// Key of the state in the wizard file.
+    pub key: &'a str,
+    /// Variable the answer is stored in.
+    pub name: &'a str,
+    pub message: &'a str,
+    /// Why the previous answer to this prompt was rejected, when it is
+    /// being asked again.
+    pub error: Option<&'a str>,
+}
+
+/// An option of a select-type prompt.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    pub label: String,
+    pub description: Option<String>,
+    /// Stored in the context when the option is picked.
+    pub value: Value,
+}
+
+impl Item {
+    /// An option whose label is also its value.
+    pub fn new(label: impl Into<String>) -> Self {
+        let label = label.into();
+        Self {
+            value: Value::String(label.clone()),
+            label,
+            description: None,
+        }
+    }
+
+    /// The row shown in the terminal.
+    pub fn display(&self) -> String {
+        match &self.description {
+            Some(description) => format!("{} — {}", self.label, description),
+            None => self.label.clone(),
+        }
+    }
+
+    /// Whether `value` names this option, either by its value or by its
+    /// label.
+    pub fn matches(&self, value: &Value) -> bool {
+        self.value.loosely_equals(value)
+            || matches!(value, Value::String(s) | Value::Symbol(s) if *s == self.label)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Response<T> {
+    Submit(T),
+    /// The user asked to return to the previous prompt.
+    Back,
+}
+
+/// Source of answers for prompt states.
+///
+/// Select-type methods return indexes into `options`, which show each
+/// option's label and description. `default` is the evaluated `default` of
+/// the prompt, if it has one.
+pub trait PromptBackend {
+    fn text(&mut self, request: &Request, default: Option<&str>) -> Result<Response<String>>;
+    fn password(&mut self, request: &Request, default: Option<&str>) -> Result<Response<String>>;
+    fn confirm(&mut self, request: &Request, default: Option<bool>) -> Result<Response<bool>>;
+    fn select(
+        &mut self,
+        request: &Request,
+        options: &[Item],
+        default: Option<usize>,
+    ) -> Result<Response<usize>>;
+    fn multi_select(
+        &mut self,
+        request: &Request,
+        options: &[Item],
+        default: Option<&[usize]>,
+    ) -> Result<Response<Vec<usize>>>;
+    fn fuzzy_select(
+        &mut self,
+        request: &Request,
+        options: &[Item],
+        default: Option<usize>,
+    ) -> Result<Response<usize>>;
+}
+
+impl<B: PromptBackend + ?Sized> PromptBackend for &mut B {
+    fn text(&mut self, request: &Request, default: Option<&str>) -> Result<Response<String>> {
+        (**self).text(request, default)
+    }
+
+    fn password(&mut self, request: &Request, default: Option<&str>) -> Result<Response<String>> {
+        (**self).password(request, default)
+    }
+
+    fn confirm(&mut self, request: &Request, default: Option<bool>) -> Result<Response<bool>> {
+        (**self).confirm(request, default)
+    }
+
+    fn select(&mut self, request: &Request, options: &[Item], default: Option<usize>) -> Result<Response<usize>> {
+        (**self).select(request, options, default)
+    }
+
+    fn multi_select(&mut self, request: &Request, options: &[Item], default: Option<&[usize]>) -> Result<Response<Vec<usize>>> {
+        (**self).multi_select(request, options, default)
+    }
+
+    fn fuzzy_select(&mut self, request: &Request, options: &[Item], default: Option<usize>) -> Result<Response<usize>> {
+        (**self).fuzzy_select(request, options, default)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("back")]
+struct BackRequested;
+
+/// Wraps the renderer of a promkit preset so that the back key ends the
+/// prompt before the preset's own keymap sees it.
+struct WithBack<'a, R> {
+    renderer: &'a mut R,
+    back_key: KeyBinding,
+}
+
+impl<R: Finalizer> Finalizer for WithBack<'_, R> {
+    type Return = R::Return;
+
+    fn finalize(&mut self) -> anyhow::Result<Self::Return> {
+        self.renderer.finalize()
+    }
+}
+
+impl<R: Renderer> Renderer for WithBack<'_, R> {
+    fn create_panes(&self, width: u16, height: u16) -> Vec<Pane> {
+        self.renderer.create_panes(width, height)
+    }
+
+    fn evaluate(&mut self, event: &Event) -> anyhow::Result<PromptSignal> {
+        if self.back_key.matches(event) {
+            return Err(anyhow::Error::new(BackRequested));
+        }
+        self.renderer.evaluate(event)
+    }
+}
+
+fn finish<T>(result: anyhow::Result<T>) -> Result<Response<T>> {
+    match result {
+        Ok(value) => Ok(Response::Submit(value)),
+        Err(e) if e.is::<BackRequested>() => Ok(Response::Back),
+        Err(e) => Err(Error::FailedToRunPrompt(e.to_string())),
+    }
+}
+
+/// Puts `default` into the input buffer so that submitting right away
+/// accepts it, and shows why the previous answer was rejected.
+fn prefill(p: &mut promkit::Prompt<readline::render::Renderer>, request: &Request, default: Option<&str>) {
+    if let Some(default) = default {
+        p.renderer.text_editor_snapshot.after_mut().texteditor.replace(default);
+    }
+    if let Some(error) = request.error {
+        p.renderer.error_message_snapshot.after_mut().text = error.to_string();
+    }
+}
+
+/// The rows a fuzzy select shows for `query`, each with the index of its
+/// option, so that options sharing a label stay apart.
+fn fuzzy_matches<'a>(query: &'a str, labels: &'a [String]) -> impl Iterator<Item = (usize, &'a String)> {
+    let query = query.to_lowercase();
+    labels
+        .iter()
+        .enumerate()
+        .filter(move |(_, label)| label.to_lowercase().contains(&query))
+}
+
+/// Interactive terminal prompts rendered with promkit.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PromkitBackend {
+    back_key: KeyBinding,
+}
+
+impl PromkitBackend {
+    pub fn new(back_key: KeyBinding) -> Self {
+        Self { back_key }
+    }
+
+    /// Runs `p` until it is submitted or the back key is pressed.
+    fn run<R: Renderer>(&self, p: &mut promkit::Prompt<R>) -> Result<Response<R::Return>> {
+        let mut with_back = promkit::Prompt {
+            renderer: WithBack {
+                renderer: &mut p.renderer,
+                back_key: self.back_key,
+            },
+        };
+        finish(with_back.run())
+    }
+}
+
+impl PromptBackend for PromkitBackend {
+    fn text(&mut self, request: &Request, default: Option<&str>) -> Result<Response<String>> {
+        let mut p = Readline::default()
+            .title(request.message)
+            .prompt()
+            .map_err(|e| Error::FailedToCreatePrompt(e.to_string()))?;
+        prefill(&mut p, request, default);
+
+        self.run(&mut p)
+    }
+
+    fn password(&mut self, request: &Request, default: Option<&str>) -> Result<Response<String>> {
+        let mut p = Readline::default()
+            .title(request.message)
+            .mask('*')
+            .prompt()
+            .map_err(|e| Error::FailedToCreatePrompt(e.to_string()))?;
+        prefill(&mut p, request, default);
+
+        self.run(&mut p)
+    }
+
+    fn confirm(&mut self, request: &Request, default: Option<bool>) -> Result<Response<bool>> {
+        let mut p = Readline::default()
+            .prefix(format!("{} (y/n) ", request.message))
+            .validator(
+                |text| ["yes", "no", "y", "n", "Y", "N"].contains(&text),
+                |_| String::from("Please type 'y' or 'n' as an answer"),
+            )
+            .prompt()
+            .map_err(|e| Error::FailedToCreatePrompt(e.to_string()))?;
+        prefill(&mut p, request, default.map(|yes| if yes { "y" } else { "n" }));
+
+        let Response::Submit(result) = self.run(&mut p)? else {
+            return Ok(Response::Back);
+        };
+        match result.to_lowercase().as_str() {
+            "yes" | "y" | "true" => Ok(Response::Submit(true)),
+            "no" | "n" | "false" => Ok(Response::Submit(false)),
+            _ => Err(Error::FailedToRunPrompt(format!("Invalid confirmation response: {}", result))),
+        }
+    }
+
+    fn select(&mut self, request: &Request, options: &[Item], default: Option<usize>) -> Result<Response<usize>> {
+        let mut p = Listbox::new(options.iter().map(Item::display))
+            .title(request.message)
+                .prompt()
+            .map_err(|e| Error::FailedToCreatePrompt(e.to_string()))?;
+        for _ in 0..default.unwrap_or(0) {
+            p.renderer.listbox_state.listbox.forward();
+        }
+
+        match self.run(&mut p)? {
+            Response::Submit(_) => Ok(Response::Submit(p.renderer.listbox_state.listbox.position())),
+            Response::Back => Ok(Response::Back),
+        }
+    }
+
+    fn multi_select(&mut self, request: &Request, options: &[Item], default: Option<&[usize]>) -> Result<Response<Vec<usize>>> {
+        let default = default.unwrap_or_default();
+        let items = options
+            .iter()
+            .enumerate()
+            .map(|(i, o)| (o.display(), default.contains(&i)));
+        let mut p = Checkbox::new_with_checked(items)
+            .title(request.message)
+                .prompt()
+            .map_err(|e| Error::FailedToCreatePrompt(e.to_string()))?;
+        // The rendered checkbox knows which rows were picked, which stays
+        // correct even when two options share the same label.
+        match self.run(&mut p)? {
+            Response::Submit(_) => {
+                let mut picked: Vec<usize> = p
+                    .renderer
+                    .checkbox_state
+                    .checkbox
+                    .picked_indexes()
+                    .iter()
+                    .copied()
+                    .collect();
+                picked.sort();
+                Ok(Response::Submit(picked))
+            }
+            Response::Back => Ok(Response::Back),
+        }
+    }
+
+    fn fuzzy_select(&mut self, request: &Request, options: &[Item], default: Option<usize>) -> Result<Response<usize>> {
+        let labels: Vec<String> = options.iter().map(Item::display).collect();
+        let mut p = QuerySelector::new(&labels, |input, opts| {
+            fuzzy_matches(input, opts).map(|(_, opt)| opt.clone()).collect()
+        })
+        .title(request.message)
+        .prompt()
+        .map_err(|e| Error::FailedToCreatePrompt(e.to_string()))?;
+        for _ in 0..default.unwrap_or(0) {
+            p.renderer.listbox_snapshot.after_mut().listbox.forward();
+        }
+
+        if let Response::Back = self.run(&mut p)? {
+            return Ok(Response::Back);
+        }
+        // The listbox only holds the labels left by the filter, so the picked
+        // row is mapped back through the same filter.
+        let query = p.renderer.text_editor_snapshot.after().texteditor.text_without_cursor().to_string();
+        let row = p.renderer.listbox_snapshot.after().listbox.position();
+        let picked = fuzzy_matches(&query, &labels).nth(row).map(|(i, _)| i);
+        picked
+            .map(Response::Submit)
+            .ok_or_else(|| Error::FailedToRunPrompt("No option matches the query".to_string()))
+    }
+}
+
+/// The response of a non-interactive backend that has no answer for
+/// `request`: the default, unless the last answer was rejected.
+pub(crate) fn fallback<T>(request: &Request, default: Option<T>) -> Result<Response<T>> {
+    if let Some(error) = request.error {
+        return Err(invalid(request, error.to_string()));
+    }
+    default.map(Response::Submit).ok_or_else(|| {
+        Error::MissingAnswer(format!(
+            "state '{}' (variable '{}')",
+            request.key, request.name
+        ))
+    })
+}
+
+fn invalid(request: &Request, reason: String) -> Error {
+    Error::InvalidAnswer(format!("state '{}': {}", request.key, reason))
+}
+
+/// Converts a recorded answer into the input of a text or password prompt.
+pub(crate) fn answer_text(request: &Request, answer: &Value) -> Result<String> {
+    match answer {
+        Value::String(s) => Ok(s.clone()),
+        Value::Number(n) => Ok(n.to_string()),
+        _ => Err(invalid(request, format!("expected a string, got {:?}", answer))),
+    }
+}
+
+pub(crate) fn answer_confirm(request: &Request, answer: &Value) -> Result<bool> {
+    match answer {
+        Value::Boolean(b) => Ok(*b),
+        _ => Err(invalid(request, format!("expected a boolean, got {:?}", answer))),
+    }
+}
+
+/// Finds the option an answer names by its value or label.
+pub(crate) fn answer_select(request: &Request, answer: &Value, options: &[Item]) -> Result<usize> {
+    options.iter().position(|o| o.matches(answer)).ok_or_else(|| {
+        let labels: Vec<_> = options.iter().map(|o| o.label.as_str()).collect();
+        invalid(
+            request,
+            format!("'{}' is not one of the options [{}]", to_text(answer), labels.join(", ")),
+        )
+    })
+}
+
+pub(crate) fn answer_multi_select(
+    request: &Request,
+    answer: &Value,
+    options: &[Item],
+) -> Result<Vec<usize>> {
+    match answer {
+        Value::Array(items) => items
+            .iter()
+            .map(|item| answer_select(request, item, options))
+            .collect(),
+        _ => Err(invalid(request, format!("expected an array, got {:?}", answer))),
+    }
+}
+
+/// Answers a prompt with the answer it was given before, converted the way
+/// a recorded answer is. The prompt is asked through `backend` instead when
+/// that answer no longer fits its options or the prompt rejects it.
+pub struct Replay<'a> {
+    answer: Option<Value>,
+    backend: &'a mut dyn PromptBackend,
+}
+
+impl<'a> Replay<'a> {
+    pub fn new(answer: Value, backend: &'a mut dyn PromptBackend) -> Self {
+        Self {
+            answer: Some(answer),
+            backend,
+        }
+    }
+
+    /// The recorded answer, unless it was already given.
+    fn respond<T>(&mut self, request: &Request, convert: impl FnOnce(&Value) -> Result<T>) -> Option<T> {
+        let answer = self.answer.take().filter(|_| request.error.is_none())?;
+        convert(&answer).ok()
+    }
+}
+
+impl PromptBackend for Replay<'_> {
+    fn text(&mut self, request: &Request, default: Option<&str>) -> Result<Response<String>> {
+        match self.respond(request, |answer| answer_text(request, answer)) {
+            Some(answer) => Ok(Response::Submit(answer)),
+            None => self.backend.text(request, default),
+        }
+    }
+
+    fn password(&mut self, request: &Request, default: Option<&str>) -> Result<Response<String>> {
+        match self.respond(request, |answer| answer_text(request, answer)) {
+            Some(answer) => Ok(Response::Submit(answer)),
+            None => self.backend.password(request, default),
+        }
+    }
+
+    fn confirm(&mut self, request: &Request, default: Option<bool>) -> Result<Response<bool>> {
+        match self.respond(request, |answer| answer_confirm(request, answer)) {
+            Some(answer) => Ok(Response::Submit(answer)),
+            None => self.backend.confirm(request, default),
+        }
+    }
+
+    fn select(&mut self, request: &Request, options: &[Item], default: Option<usize>) -> Result<Response<usize>> {
+        match self.respond(request, |answer| answer_select(request, answer, options)) {
+            Some(answer) => Ok(Response::Submit(answer)),
+            None => self.backend.select(request, options, default),
+        }
+    }
+
+    fn multi_select(&mut self, request: &Request, options: &[Item], default: Option<&[usize]>) -> Result<Response<Vec<usize>>> {
+        match self.respond(request, |answer| answer_multi_select(request, answer, options)) {
+            Some(answer) => Ok(Response::Submit(answer)),
+            None => self.backend.multi_select(request, options, default),
+        }
+    }
+
+    fn fuzzy_select(&mut self, request: &Request, options: &[Item], default: Option<usize>) -> Result<Response<usize>> {
+        match self.respond(request, |answer| answer_select(request, answer, options)) {
+            Some(answer) => Ok(Response::Submit(answer)),
+            None => self.backend.fuzzy_select(request, options, default),
+        }
+    }
+}
+
+/// A scripted input for `ScriptedBackend`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Scripted {
+    Answer(Value),
+    Back,
+}
+
+/// In-memory backend that replays queued inputs, for driving a wizard
+/// without a terminal.
+///
+/// Inputs are queued per state key or variable name and consumed in order;
+/// running out of inputs for a prompt accepts its default, or is an error
+/// rather than a hang when it has none or the last input was rejected.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptedBackend {
+    inputs: HashMap<String, VecDeque<Scripted>>,
+}
+
+impl ScriptedBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues an answer for the state key or variable name `key`.
+    pub fn answer(mut self, key: impl Into<String>, value: Value) -> Self {
+        self.push(key, Scripted::Answer(value));
+        self
+    }
+
+    /// Queues a back navigation for the state key or variable name `key`.
+    pub fn back(mut self, key: impl Into<String>) -> Self {
+        self.push(key, Scripted::Back);
+        self
+    }
+
+    pub fn push(&mut self, key: impl Into<String>, input: Scripted) {
+        self.inputs.entry(key.into()).or_default().push_back(input);
+    }
+
+    /// Inputs that have been queued but not consumed yet.
+    pub fn remaining(&self) -> usize {
+        self.inputs.values().map(|q| q.len()).sum()
+    }
+
+    /// Answers from the next queued input, or with `default` when nothing
+    /// is queued for the prompt.
+    fn respond<T>(
+        &mut self,
+        request: &Request,
+        default: Option<T>,
+        convert: impl FnOnce(&Value) -> Result<T>,
+    ) -> Result<Response<T>> {
+        let input = [request.key, request.name]
+            .iter()
+            .find_map(|k| self.inputs.get_mut(*k).and_then(|q| q.pop_front()));
+
+        match input {
+            Some(Scripted::Answer(value)) => Ok(Response::Submit(convert(&value)?)),
+            Some(Scripted::Back) => Ok(Response::Back),
+            None => fallback(request, default),
+        }
+    }
+}
+
+impl PromptBackend for ScriptedBackend {
+    fn text(&mut self, request: &Request, default: Option<&str>) -> Result<Response<String>> {
+        self.respond(request, default.map(str::to_string), |answer| {
+            answer_text(request, answer)
+        })
+    }
+
+    fn password(&mut self, request: &Request, default: Option<&str>) -> Result<Response<String>> {
+        self.text(request, default)
+    }
+
+    fn confirm(&mut self, request: &Request, default: Option<bool>) -> Result<Response<bool>> {
+        self.respond(request, default, |answer| answer_confirm(request, answer))
+    }
+
+    fn select(&mut self, request: &Request, options: &[Item], default: Option<usize>) -> Result<Response<usize>> {
+        self.respond(request, default, |answer| answer_select(request, answer, options))
+    }
+
+    fn multi_select(&mut self, request: &Request, options: &[Item], default: Option<&[usize]>) -> Result<Response<Vec<usize>>> {
+        self.respond(request, default.map(<[usize]>::to_vec), |answer| {
+            answer_multi_select(request, answer, options)
+        })
+    }
+
+    fn fuzzy_select(&mut self, request: &Request, options: &[Item], default: Option<usize>) -> Result<Response<usize>> {
+        self.select(request, options, default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use promkit::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    use super::*;
+
+    /// Counts the events that reach the preset's keymap.
+    struct Counter(usize);
+
+    impl Finalizer for Counter {
+        type Return = usize;
+
+        fn finalize(&mut self) -> anyhow::Result<usize> {
+            Ok(self.0)
+        }
+    }
+
+    impl Renderer for Counter {
+        fn create_panes(&self, _: u16, _: u16) -> Vec<Pane> {
+            vec![]
+        }
+
+        fn evaluate(&mut self, _: &Event) -> anyhow::Result<PromptSignal> {
+            self.0 += 1;
+            Ok(PromptSignal::Continue)
+        }
+    }
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> Event {
+        Event::Key(KeyEvent::new(code, modifiers))
+    }
+
+    #[test]
+    fn test_with_back() {
+        let mut counter = Counter(0);
+        let mut renderer = WithBack {
+            renderer: &mut counter,
+            back_key: KeyBinding::new(KeyCode::Char('b'), KeyModifiers::CONTROL),
+        };
+
+        assert!(renderer.evaluate(&key(KeyCode::Char('b'), KeyModifiers::NONE)).is_ok());
+        assert!(renderer.evaluate(&key(KeyCode::Esc, KeyModifiers::NONE)).is_ok());
+        let back = renderer.evaluate(&key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        assert!(matches!(finish(back), Ok(Response::Back)));
+        assert_eq!(counter.0, 2);
+    }
+
+    #[test]
+    fn test_fuzzy_matches() {
+        let labels = vec!["Prod".to_string(), "dev".to_string(), "prod".to_string()];
+        let matched: Vec<_> = fuzzy_matches("PRO", &labels).map(|(i, _)| i).collect();
+        assert_eq!(matched, vec![0, 2]);
+    }
+}
@@ -0,0 +1,76 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use crate::{
+    error::{Error, Result},
+    eval::{eval, Context},
+    parser::{Expr, Value},
+    state::Node,
+    toml::{canonical, load_from},
+};
+
+/// Runs another wizard file as a sub-wizard.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call {
+    pub wizard: PathBuf,
+    /// Expressions evaluated in the caller's context to build the `args` of
+    /// the sub-wizard.
+    pub args: HashMap<String, Expr>,
+    /// Variables of the sub-wizard to hand back; all of them when `None`.
+    pub returns: Option<Vec<String>>,
+}
+
+impl Call {
+    /// Returns the wizards being run once the called one is entered, or an
+    /// error if it is already being run. `wizards` holds the canonical
+    /// paths of the calling wizards, outermost first.
+    pub fn enter(&self, wizards: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        let wizard = canonical(&self.wizard);
+        if let Some(i) = wizards.iter().position(|w| *w == wizard) {
+            let cycle = wizards[i..]
+                .iter()
+                .chain(std::iter::once(&wizard))
+                .map(|w| w.display().to_string())
+                .collect::<Vec<_>>();
+            return Err(Error::CallError(format!("call cycle: {}", cycle.join(" -> "))));
+        }
+        Ok(wizards.iter().cloned().chain(std::iter::once(wizard)).collect())
+    }
+
+    pub fn load(&self) -> Result<(String, HashMap<String, Node>)> {
+        let content = std::fs::read_to_string(&self.wizard)
+            .map_err(|e| Error::FileError(format!("{}: {}", self.wizard.display(), e)))?;
+        load_from(&content, &self.wizard)
+    }
+
+    pub fn args(&self, context: &Context) -> Result<HashMap<String, Value>> {
+        self.args
+            .iter()
+            .map(|(name, expr)| Ok((name.clone(), eval(expr, context)?)))
+            .collect()
+    }
+
+    /// Collects the returned variables from the final context of the
+    /// sub-wizard into an object.
+    pub fn returns(&self, context: &Context) -> Result<Value> {
+        let variables = context.get_context();
+        let returned = match &self.returns {
+            Some(names) => names
+                .iter()
+                .map(|name| match variables.get(name) {
+                    Some(value) => Ok((name.clone(), value.clone())),
+                    None => Err(Error::UndefinedVariable(format!(
+                        "{} (returned from {})",
+                        name,
+                        self.wizard.display()
+                    ))),
+                })
+                .collect::<Result<_>>()?,
+            None => variables
+                .iter()
+                .filter(|(name, _)| name.as_str() != "args")
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+        };
+        Ok(Value::Object(returned))
+    }
+}
@@ -0,0 +1,626 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+    path::PathBuf,
+};
+
+use thiserror::Error;
+
+use crate::{
+    call::Call,
+    condition::WILDCARD,
+    parser::{BinOp, Expr, UnaryOp, Value},
+    prompt::ANSWER_VARIABLE,
+    state::{Node, State, Target},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum Issue {
+    #[error("start state '{0}' does not exist")]
+    InvalidStart(String),
+
+    #[error("transition to undefined state '{0}'")]
+    DanglingTransition(String),
+
+    #[error("state is unreachable from the start state")]
+    Unreachable,
+
+    #[error("no path from this state reaches a done state")]
+    NoPathToDone,
+
+    #[error("condition is missing the '{0}' branch")]
+    MissingBranch(String),
+
+    #[error("variable '${0}' is never assigned on any path to this state")]
+    UndefinedVariable(String),
+
+    #[error("called wizard is invalid: {0}")]
+    InvalidCall(String),
+}
+
+impl Issue {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Issue::Unreachable => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub state: String,
+    pub issue: Issue,
+}
+
+impl Diagnostic {
+    pub fn severity(&self) -> Severity {
+        self.issue.severity()
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: [{}] {}", self.severity(), self.state, self.issue)
+    }
+}
+
+/// Statically validates a loaded wizard and returns every problem found,
+/// ordered by state key.
+pub fn check(start: &str, nodes: &HashMap<String, Node>) -> Vec<Diagnostic> {
+    check_called(start, nodes, &[])
+}
+
+/// `wizards` holds the canonical paths of the wizards calling this one, so
+/// that checking a call cycle does not recurse forever.
+fn check_called(start: &str, nodes: &HashMap<String, Node>, wizards: &[PathBuf]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    if !nodes.contains_key(start) {
+        diagnostics.push(Diagnostic {
+            state: start.to_string(),
+            issue: Issue::InvalidStart(start.to_string()),
+        });
+    }
+
+    let mut successors: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (key, node) in nodes {
+        let mut resolved = Vec::new();
+        for (_, target) in node.state.targets() {
+            match static_target(target) {
+                Some(to) => match nodes.get_key_value(to) {
+                    Some((to, _)) => resolved.push(to.as_str()),
+                    None => diagnostics.push(Diagnostic {
+                        state: key.clone(),
+                        issue: Issue::DanglingTransition(to.clone()),
+                    }),
+                },
+                // A dynamic target may resolve to any state at runtime.
+                None => resolved.extend(nodes.keys().map(|k| k.as_str())),
+            }
+        }
+        successors.insert(key.as_str(), resolved);
+
+        if let State::Condition(condition) = &node.state {
+            if is_boolean(&condition.condition) && !condition.branches.contains_key(WILDCARD) {
+                for branch in ["true", "false"] {
+                    if !condition.branches.contains_key(branch) {
+                        diagnostics.push(Diagnostic {
+                            state: key.clone(),
+                            issue: Issue::MissingBranch(branch.to_string()),
+                        });
+                    }
+                }
+            }
+        }
+
+        if let State::Call(call, _) = &node.state {
+            diagnostics.extend(check_call(call, wizards).into_iter().map(|problem| Diagnostic {
+                state: key.clone(),
+                issue: Issue::InvalidCall(problem),
+            }));
+        }
+    }
+
+    let reachable = reachable_from(start, &successors);
+    let finishing = reaching_done(nodes, &successors);
+    for key in nodes.keys() {
+        if !reachable.contains(key.as_str()) {
+            diagnostics.push(Diagnostic {
+                state: key.clone(),
+                issue: Issue::Unreachable,
+            });
+        } else if !finishing.contains(key.as_str()) {
+            diagnostics.push(Diagnostic {
+                state: key.clone(),
+                issue: Issue::NoPathToDone,
+            });
+        }
+    }
+
+    let assigned = assigned_variables(start, nodes, &successors);
+    for (key, node) in nodes {
+        let Some(defined) = assigned.get(key.as_str()) else {
+            continue;
+        };
+
+        let mut used = HashSet::new();
+        for expr in expressions(&node.state) {
+            collect_variables(expr, &mut used);
+        }
+
+        for variable in used {
+            if variable != "args"
+                && variable != ANSWER_VARIABLE
+                && !defined.contains(variable.as_str())
+            {
+                diagnostics.push(Diagnostic {
+                    state: key.clone(),
+                    issue: Issue::UndefinedVariable(variable),
+                });
+            }
+        }
+    }
+
+    diagnostics.sort_by(|a, b| {
+        (&a.state, a.issue.to_string()).cmp(&(&b.state, b.issue.to_string()))
+    });
+    diagnostics
+}
+
+/// Loads and checks a called wizard, returning its errors prefixed with its
+/// path.
+fn check_call(call: &Call, wizards: &[PathBuf]) -> Vec<String> {
+    let wizards = match call.enter(wizards) {
+        Ok(wizards) => wizards,
+        Err(e) => return vec![e.to_string()],
+    };
+    let (start, nodes) = match call.load() {
+        Ok(loaded) => loaded,
+        Err(e) => return vec![format!("{}: {}", call.wizard.display(), e)],
+    };
+    check_called(&start, &nodes, &wizards)
+        .into_iter()
+        .filter(|d| d.severity() == Severity::Error)
+        .map(|d| format!("{}: [{}] {}", call.wizard.display(), d.state, d.issue))
+        .collect()
+}
+
+/// The state key a transition leads to, or `None` when it is only known at
+/// runtime.
+fn static_target<'a>(target: Target<&'a String, &'a Expr>) -> Option<&'a String> {
+    match target {
+        Target::Key(to) => Some(to),
+        Target::Expr(Expr::Value(Value::String(s) | Value::Symbol(s))) => Some(s),
+        Target::Expr(_) => None,
+    }
+}
+
+fn expressions(state: &State) -> Vec<&Expr> {
+    match state {
+        State::Prompt(prompt, _) => prompt.expressions(),
+        State::Condition(condition) => std::iter::once(&condition.condition)
+            .chain(condition.branches.values())
+            .collect(),
+        State::Goto(goto) => vec![&goto.target],
+        State::Switch(switch) => switch.cases.iter().map(|(when, _)| when).collect(),
+        State::Set(expr, _) => vec![expr],
+        State::Call(call, _) => call.args.values().collect(),
+        State::Review(review, _) => review.message.expressions().collect(),
+        State::Exec(exec) => exec
+            .command
+            .iter()
+            .chain(&exec.cwd)
+            .chain(exec.env.values())
+            .flat_map(|template| template.expressions())
+            .collect(),
+        State::Collect(collect) => collect
+            .max
+            .iter()
+            .chain(collect.confirm.iter().flat_map(|confirm| confirm.expressions()))
+            .collect(),
+        State::Foreach(foreach) => vec![&foreach.over],
+        State::Remove(_) | State::Done => vec![],
+    }
+}
+
+fn collect_variables(expr: &Expr, variables: &mut HashSet<String>) {
+    match expr {
+        Expr::UnaryOp {
+            op: UnaryOp::Dollar,
+            expr,
+        } => {
+            if let Expr::Value(Value::Symbol(name)) = expr.as_ref() {
+                variables.insert(name.clone());
+            }
+        }
+        Expr::UnaryOp { expr, .. } => collect_variables(expr, variables),
+        Expr::BinaryOp { left, right, .. } => {
+            collect_variables(left, variables);
+            collect_variables(right, variables);
+        }
+        Expr::Function { args, .. } => {
+            for arg in args {
+                collect_variables(arg, variables);
+            }
+        }
+        Expr::Value(_) => {}
+    }
+}
+
+fn is_boolean(expr: &Expr) -> bool {
+    match expr {
+        Expr::Value(Value::Boolean(_)) => true,
+        Expr::UnaryOp {
+            op: UnaryOp::Not, ..
+        } => true,
+        Expr::BinaryOp { op, .. } => matches!(
+            op,
+            BinOp::Eq
+                | BinOp::NotEq
+                | BinOp::Gt
+                | BinOp::Ge
+                | BinOp::Lt
+                | BinOp::Le
+                | BinOp::And
+                | BinOp::Or
+        ),
+        _ => false,
+    }
+}
+
+fn reachable_from<'a>(start: &'a str, successors: &HashMap<&'a str, Vec<&'a str>>) -> HashSet<&'a str> {
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::new();
+    if successors.contains_key(start) {
+        queue.push_back(start);
+    }
+
+    while let Some(key) = queue.pop_front() {
+        if !visited.insert(key) {
+            continue;
+        }
+        for next in &successors[key] {
+            queue.push_back(next);
+        }
+    }
+
+    visited
+}
+
+fn reaching_done<'a>(
+    nodes: &'a HashMap<String, Node>,
+    successors: &HashMap<&'a str, Vec<&'a str>>,
+) -> HashSet<&'a str> {
+    let mut predecessors: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (from, targets) in successors {
+        for to in targets {
+            predecessors.entry(to).or_default().push(from);
+        }
+    }
+
+    let mut visited = HashSet::new();
+    let mut queue: VecDeque<&str> = nodes
+        .iter()
+        .filter(|(_, node)| node.state == State::Done)
+        .map(|(key, _)| key.as_str())
+        .collect();
+
+    while let Some(key) = queue.pop_front() {
+        if !visited.insert(key) {
+            continue;
+        }
+        if let Some(from) = predecessors.get(key) {
+            queue.extend(from.iter().copied());
+        }
+    }
+
+    visited
+}
+
+/// Forward data-flow over the state graph: for every reachable state, the
+/// set of variables that are assigned on at least one path leading to it.
+fn assigned_variables<'a>(
+    start: &'a str,
+    nodes: &'a HashMap<String, Node>,
+    successors: &HashMap<&'a str, Vec<&'a str>>,
+) -> HashMap<&'a str, HashSet<String>> {
+    let mut assigned: HashMap<&str, HashSet<String>> = HashMap::new();
+    let Some((start, _)) = nodes.get_key_value(start) else {
+        return assigned;
+    };
+    assigned.insert(start.as_str(), HashSet::new());
+
+    let mut queue = VecDeque::from([start.as_str()]);
+    while let Some(key) = queue.pop_front() {
+        let node = &nodes[key];
+        let mut out = assigned[key].clone();
+        match &node.state {
+            State::Prompt(_, _)
+            | State::Set(_, _)
+            | State::Call(_, _)
+            | State::Exec(_)
+            | State::Collect(_) => {
+                out.insert(node.name.clone());
+            }
+            State::Foreach(foreach) => {
+                out.extend([node.name.clone(), foreach.item.clone(), foreach.index.clone()]);
+            }
+            State::Remove(_) => {
+                out.remove(&node.name);
+            }
+            _ => {}
+        }
+
+        for next in &successors[key] {
+            let first_visit = !assigned.contains_key(next);
+            let entry = assigned.entry(next).or_default();
+            let before = entry.len();
+            entry.extend(out.iter().cloned());
+            if first_visit || entry.len() != before {
+                queue.push_back(next);
+            }
+        }
+    }
+
+    assigned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::toml::load;
+
+    fn issues(content: &str) -> Vec<(String, Issue)> {
+        let (start, nodes) = load(content).unwrap();
+        check(&start, &nodes)
+            .into_iter()
+            .map(|d| (d.state, d.issue))
+            .collect()
+    }
+
+    #[test]
+    fn test_check_valid() {
+        let content = r#"
+            start = "name"
+
+            [state.name]
+            type = "text"
+            name = "name"
+            message = "What is your name?"
+            to = "check"
+
+            [state.check]
+            type = "condition"
+            condition = "$name == 'root'"
+            branches = { true = "end", false = "end" }
+
+            [state.end]
+            type = "done"
+        "#;
+
+        assert_eq!(issues(content), vec![]);
+    }
+
+    #[test]
+    fn test_check_dangling_and_unreachable() {
+        let content = r#"
+            start = "name"
+
+            [state.name]
+            type = "text"
+            name = "name"
+            message = "What is your name?"
+            to = "missing"
+
+            [state.orphan]
+            type = "done"
+        "#;
+
+        assert_eq!(
+            issues(content),
+            vec![
+                ("name".to_string(), Issue::NoPathToDone),
+                (
+                    "name".to_string(),
+                    Issue::DanglingTransition("missing".to_string())
+                ),
+                ("orphan".to_string(), Issue::Unreachable),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_missing_branch() {
+        let content = r#"
+            start = "check"
+
+            [state.check]
+            type = "condition"
+            condition = "$args.age > 18"
+            branches = { true = "end" }
+
+            [state.end]
+            type = "done"
+        "#;
+
+        assert_eq!(
+            issues(content),
+            vec![("check".to_string(), Issue::MissingBranch("false".to_string()))]
+        );
+
+        let content = r#"
+            start = "check"
+
+            [state.check]
+            type = "condition"
+            condition = "$args.age > 18"
+            branches = { true = "end", _ = "end" }
+
+            [state.dispatch]
+            type = "switch"
+            cases = [{ when = "$args.age > 65", to = "end" }]
+            default = "missing"
+
+            [state.end]
+            type = "done"
+        "#;
+
+        assert_eq!(
+            issues(content),
+            vec![
+                ("dispatch".to_string(), Issue::Unreachable),
+                (
+                    "dispatch".to_string(),
+                    Issue::DanglingTransition("missing".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_undefined_variable() {
+        let content = r#"
+            start = "check"
+
+            [state.check]
+            type = "condition"
+            condition = "$age > 18"
+            branches = { true = "age", false = "end" }
+
+            [state.age]
+            type = "text"
+            name = "age"
+            message = "What is your age?"
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+
+        assert_eq!(
+            issues(content),
+            vec![(
+                "check".to_string(),
+                Issue::UndefinedVariable("age".to_string())
+            )]
+        );
+    }
+
+    #[test]
+    fn test_check_variable_assigned_on_one_path() {
+        let content = r#"
+            start = "check"
+
+            [state.check]
+            type = "condition"
+            condition = "$args.ask"
+            branches = { true = "age", false = "use" }
+
+            [state.age]
+            type = "text"
+            name = "age"
+            message = "What is your age?"
+            to = "use"
+
+            [state.use]
+            type = "set"
+            name = "next"
+            value = "$age + 1"
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+
+        assert_eq!(issues(content), vec![]);
+    }
+
+    #[test]
+    fn test_check_call() {
+        let dir = std::env::temp_dir().join(format!("promptoml-check-call-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("broken.toml"),
+            r#"
+                start = "region"
+
+                [state.region]
+                type = "text"
+                name = "region"
+                message = "Region?"
+                to = "nowhere"
+
+                [state.end]
+                type = "done"
+            "#,
+        )
+        .unwrap();
+        let wizard = dir.join("wizard.toml");
+        let content = r#"
+            start = "broken"
+
+            [state.broken]
+            type = "call"
+            name = "broken"
+            wizard = "broken.toml"
+            to = "missing"
+
+            [state.missing]
+            type = "call"
+            name = "missing"
+            wizard = "missing.toml"
+            to = "again"
+
+            [state.again]
+            type = "call"
+            name = "again"
+            wizard = "wizard.toml"
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+        std::fs::write(&wizard, content).unwrap();
+        let (start, nodes) = crate::toml::load_from(content, &wizard).unwrap();
+        let diagnostics: Vec<_> = check(&start, &nodes)
+            .into_iter()
+            .map(|d| (d.state, d.issue))
+            .collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let broken = dir.join("broken.toml").display().to_string();
+        assert!(diagnostics.contains(&(
+            "broken".to_string(),
+            Issue::InvalidCall(format!(
+                "{}: [region] transition to undefined state 'nowhere'",
+                broken
+            ))
+        )));
+        assert!(diagnostics
+            .iter()
+            .any(|(state, issue)| state == "missing"
+                && matches!(issue, Issue::InvalidCall(problem) if problem.contains("missing.toml"))));
+        // The wizard calling itself is checked once more, where the call
+        // cycle is found.
+        assert!(diagnostics
+            .iter()
+            .any(|(state, issue)| state == "again"
+                && matches!(issue, Issue::InvalidCall(problem) if problem.contains("call cycle"))));
+    }
+}
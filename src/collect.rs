@@ -0,0 +1,200 @@
+use crate::{
+    backend::{PromptBackend, Request, Response},
+    error::{Error, Result},
+    eval::{eval, Context},
+    parser::{Expr, Value},
+    rounds::{take, Rounds},
+    template::Template,
+};
+
+/// Runs the states starting at `body` again and again, appending what each
+/// round leaves in the item variable to an array.
+///
+/// The body hands control back by transitioning to the collect state. The
+/// loop ends when a round leaves an empty item, when `max` items have been
+/// collected, or when the user declines the `confirm` prompt.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Collect {
+    pub body: String,
+    /// Variable holding the item of a round; the name of the `body` state
+    /// when `None`.
+    pub item: Option<String>,
+    pub max: Option<Expr>,
+    pub confirm: Option<Template>,
+    pub to: String,
+}
+
+impl Collect {
+    /// Returns the state to enter next, which is `body` for another round
+    /// and `to` once the loop is over.
+    pub fn run(
+        &self,
+        key: &str,
+        name: &str,
+        item: &str,
+        context: &mut Context,
+        backend: &mut dyn PromptBackend,
+    ) -> Result<Response<String>> {
+        let rounds = Rounds {
+            key,
+            body: &self.body,
+            to: &self.to,
+        };
+        let count = match rounds.finished(context, name, Value::Array(Vec::new())) {
+            None => 0,
+            Some(round) => {
+                let value = take(context, item);
+                if is_empty(&value) {
+                    return Ok(Response::Submit(rounds.end(context)));
+                }
+
+                let mut items = match context.get_context().get(name) {
+                    Some(Value::Array(items)) => items.clone(),
+                    _ => Vec::new(),
+                };
+                items.push(value);
+                context.set_variable(name.to_string(), Value::Array(items));
+                round + 1
+            }
+        };
+
+        // Checked before every round, so that a `max` of 0 runs none.
+        if let Some(max) = &self.max {
+            match eval(max, context)? {
+                Value::Number(max) if count as f64 >= max => {
+                    return Ok(Response::Submit(rounds.end(context)))
+                }
+                Value::Number(_) => {}
+                value => {
+                    return Err(Error::TypeError(format!(
+                        "max of state '{}' must be a number, got {:?}",
+                        key, value
+                    )))
+                }
+            }
+        }
+
+        if let Some(confirm) = self.confirm.as_ref().filter(|_| count > 0) {
+            let message = confirm.render(context)?;
+            let request = Request {
+                key,
+                name,
+                message: &message,
+                error: None,
+            };
+            match backend.confirm(&request, None)? {
+                Response::Submit(true) => {}
+                Response::Submit(false) => return Ok(Response::Submit(rounds.end(context))),
+                Response::Back => return Ok(Response::Back),
+            }
+        }
+
+        Ok(Response::Submit(rounds.next(context, count)))
+    }
+}
+
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::String(s) => s.trim().is_empty(),
+        Value::Array(a) => a.is_empty(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{backend::ScriptedBackend, parser::parse};
+
+    fn collect(max: Option<&str>, confirm: Option<&str>) -> Collect {
+        Collect {
+            body: "tag".to_string(),
+            item: None,
+            max: max.map(|max| parse(max).unwrap()),
+            confirm: confirm.map(|confirm| Template::parse(confirm).unwrap()),
+            to: "end".to_string(),
+        }
+    }
+
+    /// Runs the loop, answering each round with the next of `items`, and
+    /// returns the states entered.
+    fn run(collect: &Collect, items: &[&str], backend: &mut ScriptedBackend, context: &mut Context) -> Vec<String> {
+        let mut items = items.iter();
+        let mut entered = Vec::new();
+        loop {
+            let Response::Submit(next) = collect.run("tags", "tags", "tag", context, backend).unwrap() else {
+                panic!("Expected submit");
+            };
+            entered.push(next.clone());
+            if next == collect.to {
+                return entered;
+            }
+            let item = items.next().unwrap();
+            context.set_variable("tag".to_string(), Value::String(item.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_collect_until_empty() {
+        let mut context = Context::new(HashMap::new());
+        let entered = run(&collect(None, None), &["a", "b", " "], &mut ScriptedBackend::new(), &mut context);
+
+        assert_eq!(entered, vec!["tag", "tag", "tag", "end"]);
+        assert_eq!(
+            context.get_context().get("tags"),
+            Some(&Value::Array(vec![
+                Value::String("a".to_string()),
+                Value::String("b".to_string())
+            ]))
+        );
+        assert_eq!(context.get_context().get("tag"), None);
+        assert_eq!(context.loop_iteration("tags"), None);
+    }
+
+    #[test]
+    fn test_collect_max() {
+        let mut context = Context::new(HashMap::new());
+        let entered = run(&collect(Some("2"), None), &["a", "b"], &mut ScriptedBackend::new(), &mut context);
+        assert_eq!(entered, vec!["tag", "tag", "end"]);
+
+        // No round runs when the loop is full from the start.
+        let mut context = Context::new(HashMap::new());
+        let entered = run(&collect(Some("0"), None), &[], &mut ScriptedBackend::new(), &mut context);
+        assert_eq!(entered, vec!["end"]);
+        assert_eq!(context.get_context().get("tags"), Some(&Value::Array(vec![])));
+
+        let mut context = Context::new(HashMap::new());
+        let result = collect(Some("'two'"), None).run("tags", "tags", "tag", &mut context, &mut ScriptedBackend::new());
+        assert_eq!(
+            result,
+            Err(Error::TypeError(
+                "max of state 'tags' must be a number, got String(\"two\")".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_collect_confirm() {
+        let mut backend = ScriptedBackend::new()
+            .answer("tags", Value::Boolean(true))
+            .answer("tags", Value::Boolean(false));
+        let mut context = Context::new(HashMap::new());
+        let entered = run(&collect(None, Some("Add another tag?")), &["a", "b"], &mut backend, &mut context);
+
+        assert_eq!(entered, vec!["tag", "tag", "end"]);
+        assert_eq!(backend.remaining(), 0);
+
+        let mut backend = ScriptedBackend::new().back("tags");
+        let mut context = Context::new(HashMap::new());
+        let collect = collect(None, Some("Add another tag?"));
+        collect.run("tags", "tags", "tag", &mut context, &mut backend).unwrap();
+        context.set_variable("tag".to_string(), Value::String("a".to_string()));
+        assert_eq!(
+            collect.run("tags", "tags", "tag", &mut context, &mut backend).unwrap(),
+            Response::Back
+        );
+    }
+}
@@ -6,6 +6,9 @@ use crate::{
     parser::{Expr, Value},
 };
 
+/// Branch taken when no other branch matches the condition.
+pub const WILDCARD: &str = "_";
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Condition {
     pub condition: Expr,
@@ -17,40 +20,17 @@ impl Condition {
         let cond = eval(&self.condition, context)?;
 
         let result = match cond {
-            Value::Boolean(b) => {
-                if b {
-                    Ok(self
-                        .branches
-                        .get("true")
-                        .ok_or(Error::MissingBranch("true".to_string()))?)
-                } else {
-                    Ok(self
-                        .branches
-                        .get("false")
-                        .ok_or(Error::MissingBranch("false".to_string()))?)
-                }
-            }
-            Value::String(s) => {
-                self.branches
-                    .get(&s)
-                    .ok_or(Error::MissingBranch(s.clone()))
-            }
-            Value::Symbol(s) => {
-                self.branches
-                    .get(&s)
-                    .ok_or(Error::MissingBranch(s.clone()))
-            }
-            Value::Number(n) => {
-                self.branches
-                    .get(&n.to_string())
-                    .ok_or(Error::MissingBranch(n.to_string()))
-            }
-            _ => Err(Error::TypeError(
-                "Condition must evaluate to a string".to_string(),
-            )),
+            Value::Boolean(b) => self.branch(&b.to_string()),
+            Value::String(s) => self.branch(&s),
+            Value::Symbol(s) => self.branch(&s),
+            Value::Number(n) => self.branch(&n.to_string()),
+            // No branch key can name these, so only the wildcard matches.
+            _ => self.branches.get(WILDCARD).ok_or_else(|| {
+                Error::TypeError("Condition must evaluate to a string".to_string())
+            }),
         }?;
 
-        let result = eval(&result, context)?;
+        let result = eval(result, context)?;
         match result {
             Value::String(s) => Ok(s),
             Value::Symbol(s) => Ok(s),
@@ -66,8 +46,18 @@ impl Condition {
             Value::Array(_) => Err(Error::TypeError(
                 "Condition must evaluate to a string".to_string(),
             )),
+            Value::Null => Err(Error::TypeError(
+                "Condition must evaluate to a string".to_string(),
+            )),
         }
     }
+
+    fn branch(&self, key: &str) -> Result<&Expr> {
+        self.branches
+            .get(key)
+            .or_else(|| self.branches.get(WILDCARD))
+            .ok_or_else(|| Error::MissingBranch(key.to_string()))
+    }
 }
 
 #[cfg(test)]
@@ -199,4 +189,39 @@ mod tests {
         let result = cond.eval(&context);
         assert_eq!(result, Err(Error::MissingBranch("true".to_string())));
     }
+
+    #[test]
+    fn test_condition_eval_wildcard() {
+        let cond = Condition {
+            condition: Expr::Value(Value::String("delete".to_string())),
+            branches: HashMap::from([
+                ("edit".to_string(), Expr::Value(Value::String("edit".to_string()))),
+                (WILDCARD.to_string(), Expr::Value(Value::String("other".to_string()))),
+            ]),
+        };
+
+        let context = Context::new(HashMap::new());
+        assert_eq!(cond.eval(&context), Ok("other".to_string()));
+    }
+
+    #[test]
+    fn test_condition_eval_wildcard_for_any_value() {
+        for value in [
+            Value::Null,
+            Value::Array(vec![Value::Number(1f64)]),
+            Value::Object(HashMap::new()),
+        ] {
+            let cond = Condition {
+                condition: Expr::Value(value),
+                branches: HashMap::from([(
+                    WILDCARD.to_string(),
+                    Expr::Value(Value::String("other".to_string())),
+                )]),
+            };
+
+            let context = Context::new(HashMap::new());
+            let result = cond.eval(&context);
+            assert_eq!(result, Ok("other".to_string()));
+        }
+    }
 }
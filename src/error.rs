@@ -14,6 +14,12 @@ pub enum Error {
     #[error("Unexpected trailing input: {0}")]
     TrailingInput(String),
 
+    #[error("Comparison operators cannot be chained: {0}")]
+    ChainedComparison(String),
+
+    #[error("Invalid template {0}")]
+    InvalidTemplate(String),
+
     #[error("Failed to create prompt: {0}")]
     FailedToCreatePrompt(String),
 
@@ -26,8 +32,35 @@ pub enum Error {
     #[error("Invalid transition: {0}")]
     InvalidTransition(String),
 
+    #[error("Arithmetic error: {0}")]
+    ArithmeticError(String),
+
     #[error("Index out of bounds: {0}")]
     IndexOutOfBounds(String),
+
+    #[error("Missing answer for {0}")]
+    MissingAnswer(String),
+
+    #[error("Invalid answer for {0}")]
+    InvalidAnswer(String),
+
+    #[error("File error: {0}")]
+    FileError(String),
+
+    #[error("Include error: {0}")]
+    IncludeError(String),
+
+    #[error("Call error: {0}")]
+    CallError(String),
+
+    #[error("Command failed: {0}")]
+    CommandError(String),
+
+    #[error("Output error: {0}")]
+    OutputError(String),
+
+    #[error("Session error: {0}")]
+    SessionError(String),
 }
 
 pub type Result<T> = std::result::Result<T, Error>; 
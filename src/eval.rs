@@ -1,18 +1,39 @@
 use crate::error::{Error, Result};
 use crate::parser::{BinOp, Expr, UnaryOp, Value};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Context {
     variables: HashMap<String, Value>,
+    /// Iterations completed by the loops that are running, by state key.
+    /// Kept here so that going back or resuming a session restores them
+    /// together with the variables.
+    #[serde(default)]
+    loops: HashMap<String, usize>,
 }
 
 impl Context {
     pub fn new(args: HashMap<String, Value>) -> Self {
         Context {
             variables: HashMap::from_iter(vec![("args".to_string(), Value::Object(args))]),
+            loops: HashMap::new(),
         }
     }
 
+    /// Iterations completed by the loop at state `key`, if it is running.
+    pub fn loop_iteration(&self, key: &str) -> Option<usize> {
+        self.loops.get(key).copied()
+    }
+
+    pub fn set_loop_iteration(&mut self, key: &str, iteration: usize) {
+        self.loops.insert(key.to_string(), iteration);
+    }
+
+    pub fn end_loop(&mut self, key: &str) {
+        self.loops.remove(key);
+    }
+
     pub fn set_variable(&mut self, name: String, value: Value) {
         self.variables.insert(name, value);
     }
@@ -55,17 +76,40 @@ pub fn eval(expr: &Expr, context: &Context) -> Result<Value> {
             }
         },
 
+        Expr::BinaryOp {
+            op: op @ (BinOp::And | BinOp::Or),
+            left,
+            right,
+        } => {
+            let name = if *op == BinOp::And { "And" } else { "Or" };
+            let operand = |expr: &Expr| match eval(expr, context)? {
+                Value::Boolean(b) => Ok(b),
+                _ => Err(Error::TypeError(format!(
+                    "{} operator requires booleans",
+                    name
+                ))),
+            };
+
+            // The right operand is only evaluated when it decides the result.
+            let left = operand(left)?;
+            match (op, left) {
+                (BinOp::And, false) => Ok(Value::Boolean(false)),
+                (BinOp::Or, true) => Ok(Value::Boolean(true)),
+                _ => Ok(Value::Boolean(operand(right)?)),
+            }
+        }
+
         Expr::BinaryOp { op, left, right } => {
             let left_val = eval(left, context)?;
             let right_val = eval(right, context)?;
 
             match op {
-                BinOp::Eq => Ok(Value::Boolean(left_val == right_val)),
-                BinOp::NotEq => Ok(Value::Boolean(left_val != right_val)),
-                BinOp::Gt => Ok(Value::Boolean(left_val.gt(&right_val))),
-                BinOp::Ge => Ok(Value::Boolean(left_val.ge(&right_val))),
-                BinOp::Lt => Ok(Value::Boolean(left_val.lt(&right_val))),
-                BinOp::Le => Ok(Value::Boolean(left_val.le(&right_val))),
+                BinOp::Eq => Ok(Value::Boolean(left_val.loosely_equals(&right_val))),
+                BinOp::NotEq => Ok(Value::Boolean(!left_val.loosely_equals(&right_val))),
+                BinOp::Gt => Ok(Value::Boolean(left_val.compare(&right_val)?.is_gt())),
+                BinOp::Ge => Ok(Value::Boolean(left_val.compare(&right_val)?.is_ge())),
+                BinOp::Lt => Ok(Value::Boolean(left_val.compare(&right_val)?.is_lt())),
+                BinOp::Le => Ok(Value::Boolean(left_val.compare(&right_val)?.is_le())),
                 BinOp::Dot => match (&left_val, &right_val) {
                     (Value::Object(obj), Value::Symbol(field)) => {
                         obj.get(field).cloned().ok_or_else(|| {
@@ -103,19 +147,20 @@ pub fn eval(expr: &Expr, context: &Context) -> Result<Value> {
                 BinOp::Sub => sub(&left_val, &right_val),
                 BinOp::Mul => mul(&left_val, &right_val),
                 BinOp::Div => div(&left_val, &right_val),
+                BinOp::And | BinOp::Or => unreachable!(),
             }
         },
 
         Expr::Function { name, args } => {
             let mut arg_vals = Vec::new();
             for arg in args {
-                let arg_val = eval(&arg, context)?;
+                let arg_val = eval(arg, context)?;
                 arg_vals.push(arg_val);
             }
 
             match name.as_str() {
                 "keys" => {
-                    if let Some(Value::Object(obj)) = arg_vals.get(0) {
+                    if let Some(Value::Object(obj)) = arg_vals.first() {
                         Ok(Value::Array(
                             obj.keys().map(|k| Value::String(k.clone())).collect(),
                         ))
@@ -124,9 +169,9 @@ pub fn eval(expr: &Expr, context: &Context) -> Result<Value> {
                     }
                 }
                 "len" => {
-                    if let Some(Value::String(s)) = arg_vals.get(0) {
+                    if let Some(Value::String(s)) = arg_vals.first() {
                         Ok(Value::Number(s.len() as f64))
-                    } else if let Some(Value::Array(arr)) = arg_vals.get(0) {
+                    } else if let Some(Value::Array(arr)) = arg_vals.first() {
                         Ok(Value::Number(arr.len() as f64))
                     } else {
                         Err(Error::TypeError(
@@ -134,8 +179,16 @@ pub fn eval(expr: &Expr, context: &Context) -> Result<Value> {
                         ))
                     }
                 }
+                "is_number" => match arg_vals.first() {
+                    Some(Value::Number(_)) => Ok(Value::Boolean(true)),
+                    Some(Value::String(s)) => Ok(Value::Boolean(s.trim().parse::<f64>().is_ok())),
+                    Some(_) => Ok(Value::Boolean(false)),
+                    None => Err(Error::TypeError(
+                        "is_number function requires an argument".to_string(),
+                    )),
+                },
                 "split" => {
-                    match (arg_vals.get(0), arg_vals.get(1)) {
+                    match (arg_vals.first(), arg_vals.get(1)) {
                         (Some(Value::String(s)), Some(Value::String(sep))) => {
                             Ok(Value::Array(s.split(sep).map(|s| Value::String(s.to_string())).collect()))
                         }
@@ -151,36 +204,53 @@ pub fn eval(expr: &Expr, context: &Context) -> Result<Value> {
     }
 }
 
+/// Rejects results that overflow to infinity, which have no JSON form.
+fn finite(n: f64, name: &str) -> Result<Value> {
+    if n.is_finite() {
+        Ok(Value::Number(n))
+    } else {
+        Err(Error::ArithmeticError(format!(
+            "{} operator produced a non-finite number",
+            name
+        )))
+    }
+}
+
 fn add(left: &Value, right: &Value) -> Result<Value> {
     match (left, right) {
-        (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l + r)),
+        (Value::Number(l), Value::Number(r)) => finite(l + r, "Add"),
         _ => Err(Error::TypeError("Add operator requires two numbers".to_string())),
     }
 }
 
 fn sub(left: &Value, right: &Value) -> Result<Value> {
     match (left, right) {
-        (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l - r)),
+        (Value::Number(l), Value::Number(r)) => finite(l - r, "Sub"),
         _ => Err(Error::TypeError("Sub operator requires two numbers".to_string())),
     }
 }
 
 fn mul(left: &Value, right: &Value) -> Result<Value> {
     match (left, right) {
-        (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l * r)),
+        (Value::Number(l), Value::Number(r)) => finite(l * r, "Mul"),
         _ => Err(Error::TypeError("Mul operator requires two numbers".to_string())),
     }
 }
 
 fn div(left: &Value, right: &Value) -> Result<Value> {
     match (left, right) {
-        (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l / r)),
+        (Value::Number(_), Value::Number(r)) if *r == 0.0 => {
+            Err(Error::ArithmeticError("Division by zero".to_string()))
+        }
+        (Value::Number(l), Value::Number(r)) => finite(l / r, "Div"),
         _ => Err(Error::TypeError("Div operator requires two numbers".to_string())),
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
+
     use super::*;
     use crate::parser::parse;
 
@@ -231,6 +301,49 @@ mod tests {
         assert_eq!(eval(&expr, &context).unwrap(), Value::Number(5.0));
     }
 
+    #[test]
+    fn test_eval_non_finite() {
+        let mut context = Context::new(HashMap::new());
+        context.set_variable("x".to_string(), Value::Number(f64::MAX));
+
+        let expr = parse("1 / 0").unwrap();
+        assert_eq!(
+            eval(&expr, &context),
+            Err(Error::ArithmeticError("Division by zero".to_string()))
+        );
+
+        let expr = parse("$x * 2").unwrap();
+        assert_eq!(
+            eval(&expr, &context),
+            Err(Error::ArithmeticError(
+                "Mul operator produced a non-finite number".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_eval_null() {
+        let mut context = Context::new(HashMap::from([("region".to_string(), Value::Null)]));
+        context.set_variable("x".to_string(), Value::Null);
+
+        let cases = [
+            ("$x == null", true),
+            ("$args.region != null", false),
+            ("$x == 0", false),
+            ("[null] == [null]", true),
+        ];
+        for (source, expected) in cases {
+            let expr = parse(source).unwrap();
+            assert_eq!(eval(&expr, &context), Ok(Value::Boolean(expected)), "{}", source);
+        }
+
+        let expr = parse("$x < 1").unwrap();
+        assert_eq!(
+            eval(&expr, &context),
+            Err(Error::TypeError("Cannot compare null with number".to_string()))
+        );
+    }
+
     #[test]
     fn test_eval_complex_arithmetic() {
         let mut context = Context::new(HashMap::new());
@@ -280,6 +393,140 @@ mod tests {
         assert_eq!(eval(&expr, &context).unwrap(), Value::String("H".to_string()));
     }
 
+    #[test]
+    fn test_eval_logical() {
+        let mut context = Context::new(HashMap::new());
+        context.set_variable("age".to_string(), Value::Number(20.0));
+        context.set_variable("country".to_string(), Value::String("JP".to_string()));
+
+        let expr = parse("$age > 18 && $country == 'JP'").unwrap();
+        assert_eq!(eval(&expr, &context).unwrap(), Value::Boolean(true));
+
+        let expr = parse("$age < 18 || $country != 'JP'").unwrap();
+        assert_eq!(eval(&expr, &context).unwrap(), Value::Boolean(false));
+
+        let expr = parse("!($age < 18) && !false").unwrap();
+        assert_eq!(eval(&expr, &context).unwrap(), Value::Boolean(true));
+    }
+
+    #[test]
+    fn test_eval_logical_short_circuit() {
+        let context = Context::new(HashMap::new());
+
+        // $missing would fail with an undefined variable if evaluated.
+        let expr = parse("false && $missing").unwrap();
+        assert_eq!(eval(&expr, &context).unwrap(), Value::Boolean(false));
+
+        let expr = parse("true || $missing").unwrap();
+        assert_eq!(eval(&expr, &context).unwrap(), Value::Boolean(true));
+
+        let expr = parse("true && $missing").unwrap();
+        assert_eq!(
+            eval(&expr, &context),
+            Err(Error::UndefinedVariable("missing".to_string()))
+        );
+
+        let expr = parse("1 || true").unwrap();
+        assert_eq!(
+            eval(&expr, &context),
+            Err(Error::TypeError("Or operator requires booleans".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_eval_comparison() {
+        let mut context = Context::new(HashMap::new());
+        context.set_variable("guess".to_string(), Value::String("42".to_string()));
+        context.set_variable("word".to_string(), Value::String("abc".to_string()));
+        context.set_variable(
+            "pair".to_string(),
+            Value::Array(vec![Value::String("42".to_string()), Value::String("abc".to_string())]),
+        );
+
+        let cases = [
+            ("$guess < 50", true),
+            ("$guess >= 42", true),
+            ("50 > $guess", true),
+            ("'apple' < 'banana'", true),
+            ("apple < 'banana'", true),
+            ("false < true", true),
+            ("$guess == 42.0", true),
+            ("$word == abc", true),
+            ("$pair == [42, abc]", true),
+            ("$guess != 42", false),
+        ];
+        for (source, expected) in cases {
+            let expr = parse(source).unwrap();
+            assert_eq!(eval(&expr, &context), Ok(Value::Boolean(expected)), "{}", source);
+        }
+
+        let cases = [
+            ("$word < 10", "Cannot compare string with number"),
+            ("true >= 'yes'", "Cannot compare boolean with string"),
+            ("[1] < 2", "Cannot compare array with number"),
+        ];
+        for (source, message) in cases {
+            let expr = parse(source).unwrap();
+            assert_eq!(
+                eval(&expr, &context),
+                Err(Error::TypeError(message.to_string())),
+                "{}",
+                source
+            );
+        }
+    }
+
+    fn value() -> impl Strategy<Value = Value> {
+        let leaf = prop_oneof![
+            any::<String>().prop_map(Value::String),
+            "-?[0-9]{1,3}(\\.[0-9]{1,2})?".prop_map(Value::String),
+            any::<f64>().prop_map(Value::Number),
+            any::<bool>().prop_map(Value::Boolean),
+            "[a-z_]{1,6}".prop_map(Value::Symbol),
+            Just(Value::Null),
+        ];
+        leaf.prop_recursive(3, 16, 4, |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..4).prop_map(Value::Array),
+                prop::collection::hash_map("[a-c]{1,2}", inner, 0..4).prop_map(Value::Object),
+            ]
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_comparison_never_panics(left in value(), right in value()) {
+            for op in [BinOp::Gt, BinOp::Ge, BinOp::Lt, BinOp::Le, BinOp::Eq, BinOp::NotEq] {
+                let expr = Expr::BinaryOp {
+                    op,
+                    left: Box::new(Expr::Value(left.clone())),
+                    right: Box::new(Expr::Value(right.clone())),
+                };
+                match eval(&expr, &Context::new(HashMap::new())) {
+                    Ok(Value::Boolean(_)) | Err(Error::TypeError(_)) => {}
+                    other => prop_assert!(false, "unexpected result {:?}", other),
+                }
+            }
+        }
+
+        #[test]
+        fn prop_comparison_is_antisymmetric(left in value(), right in value()) {
+            match (left.compare(&right), right.compare(&left)) {
+                (Ok(l), Ok(r)) => prop_assert_eq!(l, r.reverse()),
+                (Err(_), Err(_)) => {}
+                (l, r) => prop_assert!(false, "{:?} vs {:?}", l, r),
+            }
+        }
+    }
+
+    #[test]
+    fn test_value_equality_is_structural() {
+        // `==` in expressions converts between types; `Value` itself does not.
+        assert_ne!(Value::String("42".to_string()), Value::Number(42.0));
+        assert_ne!(Value::String("abc".to_string()), Value::Symbol("abc".to_string()));
+        assert!(Value::String("42".to_string()).loosely_equals(&Value::Number(42.0)));
+    }
+
     #[test]
     fn test_eval_function_keys() {
         let mut context = Context::new(HashMap::new());
@@ -289,10 +536,15 @@ mod tests {
         ])));
 
         let expr = parse("keys($obj)").unwrap();
-        assert_eq!(eval(&expr, &context).unwrap(), Value::Array(vec![
+        let Value::Array(mut keys) = eval(&expr, &context).unwrap() else {
+            panic!("Expected array");
+        };
+        // Object keys come in no particular order.
+        keys.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(keys, vec![
             Value::String("key1".to_string()),
             Value::String("key2".to_string()),
-        ]));
+        ]);
     }
 
     #[test]
@@ -318,4 +570,22 @@ mod tests {
             Value::String("World".to_string()),
         ]));
     }
+
+    #[test]
+    fn test_eval_function_is_number() {
+        let mut context = Context::new(HashMap::new());
+        context.set_variable("n".to_string(), Value::String(" 42.5".to_string()));
+        context.set_variable("s".to_string(), Value::String("abc".to_string()));
+
+        let cases = [
+            ("is_number($n)", true),
+            ("is_number($s)", false),
+            ("is_number(3)", true),
+            ("is_number(true)", false),
+        ];
+        for (source, expected) in cases {
+            let expr = parse(source).unwrap();
+            assert_eq!(eval(&expr, &context).unwrap(), Value::Boolean(expected), "{}", source);
+        }
+    }
 }
@@ -0,0 +1,273 @@
+use std::{
+    collections::HashMap,
+    io::Read,
+    process::{Command, Stdio},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    error::{Error, Result},
+    eval::Context,
+    parser::Value,
+    template::Template,
+};
+
+/// How the standard output of a command is stored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Output {
+    Raw,
+    #[default]
+    Trimmed,
+    /// One string per non-empty line.
+    Lines,
+    Json,
+}
+
+/// Runs a command and stores what it printed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exec {
+    /// The program followed by its arguments. Nothing is passed through a shell.
+    pub command: Vec<Template>,
+    pub cwd: Option<Template>,
+    pub env: HashMap<String, Template>,
+    pub timeout: Option<Duration>,
+    pub output: Output,
+    pub on_success: String,
+    /// Without it a failing command is an error.
+    pub on_failure: Option<String>,
+}
+
+struct Finished {
+    stdout: String,
+    stderr: String,
+    code: Option<i32>,
+}
+
+impl Exec {
+    /// Runs the command and returns the result to store together with the
+    /// state to enter next.
+    ///
+    /// The result is an object with `stdout`, `stderr`, `exit_code` (null
+    /// when the command did not exit by itself) and `error` (null on
+    /// success).
+    pub fn run(&self, context: &Context) -> Result<(Value, String)> {
+        let argv = self
+            .command
+            .iter()
+            .map(|arg| arg.render(context))
+            .collect::<Result<Vec<_>>>()?;
+        let cwd = self.cwd.as_ref().map(|cwd| cwd.render(context)).transpose()?;
+        let env = self
+            .env
+            .iter()
+            .map(|(k, v)| Ok((k.clone(), v.render(context)?)))
+            .collect::<Result<HashMap<_, _>>>()?;
+
+        let finished = self.execute(&argv, cwd.as_deref(), &env);
+        let (stdout, stderr, code, error) = match finished {
+            Ok(finished) => {
+                let (stdout, error) = match (finished.code, self.parse(&finished.stdout)) {
+                    (Some(0), Ok(stdout)) => (stdout, None),
+                    (Some(0), Err(e)) => (Value::String(finished.stdout), Some(e)),
+                    (code, _) => (
+                        Value::String(finished.stdout),
+                        Some(match code {
+                            Some(code) => format!("exited with status {}", code),
+                            None => "terminated by a signal".to_string(),
+                        }),
+                    ),
+                };
+                (stdout, finished.stderr, finished.code, error)
+            }
+            Err(e) => (Value::String(String::new()), String::new(), None, Some(e)),
+        };
+
+        let next = match (&error, &self.on_failure) {
+            (None, _) => self.on_success.clone(),
+            (Some(_), Some(on_failure)) => on_failure.clone(),
+            (Some(error), None) => {
+                let stderr = stderr.trim();
+                return Err(Error::CommandError(if stderr.is_empty() {
+                    format!("`{}` {}", argv.join(" "), error)
+                } else {
+                    format!("`{}` {}: {}", argv.join(" "), error, stderr)
+                }));
+            }
+        };
+
+        let result = HashMap::from([
+            ("stdout".to_string(), stdout),
+            ("stderr".to_string(), Value::String(stderr)),
+            (
+                "exit_code".to_string(),
+                code.map_or(Value::Null, |code| Value::Number(code as f64)),
+            ),
+            (
+                "error".to_string(),
+                error.map_or(Value::Null, Value::String),
+            ),
+        ]);
+        Ok((Value::Object(result), next))
+    }
+
+    fn execute(
+        &self,
+        argv: &[String],
+        cwd: Option<&str>,
+        env: &HashMap<String, String>,
+    ) -> std::result::Result<Finished, String> {
+        let (program, args) = argv.split_first().ok_or("command is empty")?;
+
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            .envs(env)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if let Some(cwd) = cwd {
+            command.current_dir(cwd);
+        }
+
+        let mut child = command
+            .spawn()
+            .map_err(|e| format!("could not be started: {}", e))?;
+        // Drain both pipes while waiting so that a chatty command cannot
+        // block on a full pipe.
+        let stdout = drain(child.stdout.take());
+        let stderr = drain(child.stderr.take());
+
+        let status = match self.timeout {
+            None => child.wait().map_err(|e| e.to_string())?,
+            Some(timeout) => {
+                let deadline = Instant::now() + timeout;
+                loop {
+                    if let Some(status) = child.try_wait().map_err(|e| e.to_string())? {
+                        break status;
+                    }
+                    if Instant::now() >= deadline {
+                        let _ = child.kill();
+                        let _ = child.wait();
+                        return Err(format!("timed out after {}s", timeout.as_secs_f64()));
+                    }
+                    thread::sleep(Duration::from_millis(10));
+                }
+            }
+        };
+
+        Ok(Finished {
+            stdout: stdout.join().unwrap_or_default(),
+            stderr: stderr.join().unwrap_or_default(),
+            code: status.code(),
+        })
+    }
+
+    fn parse(&self, stdout: &str) -> std::result::Result<Value, String> {
+        Ok(match self.output {
+            Output::Raw => Value::String(stdout.to_string()),
+            Output::Trimmed => Value::String(stdout.trim().to_string()),
+            Output::Lines => Value::Array(
+                stdout
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .map(|line| Value::String(line.to_string()))
+                    .collect(),
+            ),
+            Output::Json => serde_json::from_str::<serde_json::Value>(stdout)
+                .map(Value::from)
+                .map_err(|e| format!("printed invalid JSON: {}", e))?,
+        })
+    }
+}
+
+fn drain(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<String> {
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buffer);
+        }
+        String::from_utf8_lossy(&buffer).into_owned()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exec(command: &[&str], output: Output) -> Exec {
+        Exec {
+            command: command.iter().map(|arg| Template::parse(arg).unwrap()).collect(),
+            cwd: None,
+            env: HashMap::new(),
+            timeout: None,
+            output,
+            on_success: "ok".to_string(),
+            on_failure: Some("failed".to_string()),
+        }
+    }
+
+    fn field<'a>(value: &'a Value, name: &str) -> &'a Value {
+        match value {
+            Value::Object(o) => &o[name],
+            _ => panic!("Expected object"),
+        }
+    }
+
+    #[test]
+    fn test_exec_output() {
+        let mut context = Context::new(HashMap::new());
+        context.set_variable("name".to_string(), Value::String("main".to_string()));
+
+        let (value, next) = exec(&["printf", "${$name}\\n\\ndev\\n"], Output::Lines)
+            .run(&context)
+            .unwrap();
+        assert_eq!(next, "ok");
+        assert_eq!(
+            field(&value, "stdout"),
+            &Value::Array(vec![
+                Value::String("main".to_string()),
+                Value::String("dev".to_string()),
+            ])
+        );
+        assert_eq!(field(&value, "exit_code"), &Value::Number(0.0));
+
+        let (value, _) = exec(&["echo", "{\"n\": 1}"], Output::Json)
+            .run(&context)
+            .unwrap();
+        assert_eq!(
+            field(&value, "stdout"),
+            &Value::Object(HashMap::from([("n".to_string(), Value::Number(1.0))]))
+        );
+    }
+
+    #[test]
+    fn test_exec_failure() {
+        let context = Context::new(HashMap::new());
+
+        let (value, next) = exec(&["sh", "-c", "echo oops >&2; exit 3"], Output::Trimmed)
+            .run(&context)
+            .unwrap();
+        assert_eq!(next, "failed");
+        assert_eq!(field(&value, "exit_code"), &Value::Number(3.0));
+        assert_eq!(field(&value, "stderr"), &Value::String("oops\n".to_string()));
+
+        let mut timeout = exec(&["sleep", "5"], Output::Trimmed);
+        timeout.timeout = Some(Duration::from_millis(50));
+        let (value, next) = timeout.run(&context).unwrap();
+        assert_eq!(next, "failed");
+        assert_eq!(field(&value, "exit_code"), &Value::Null);
+
+        let mut required = exec(&["sh", "-c", "echo oops >&2; exit 3"], Output::Trimmed);
+        required.on_failure = None;
+        assert_eq!(
+            required.run(&context),
+            Err(Error::CommandError(
+                "`sh -c echo oops >&2; exit 3` exited with status 3: oops".to_string()
+            ))
+        );
+    }
+}
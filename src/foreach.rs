@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+
+use crate::{
+    error::{Error, Result},
+    eval::{eval, Context},
+    parser::{Expr, Value},
+    rounds::{take, Rounds},
+    template::to_text,
+};
+
+/// Visits the elements of an array, or the keys of an object in sorted
+/// order, running `body` with `item` and `index` bound to each in turn.
+/// The last state of `body` should lead back here.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Foreach {
+    /// Evaluated again on every round, so the body should not change it.
+    pub over: Expr,
+    /// Variable bound to the current element.
+    pub item: String,
+    /// Variable bound to the position of the current element.
+    pub index: String,
+    pub body: String,
+    /// Variables of a round to gather; the name of the `body` state when
+    /// `None`. A single variable is gathered as is, several as an object.
+    pub collect: Option<Vec<String>>,
+    /// Gathers into an object keyed by the text of each element instead of
+    /// an array. Always the case when iterating over an object.
+    pub keyed: bool,
+    pub to: String,
+}
+
+impl Foreach {
+    /// Files the answers of the element just visited under `name`, then
+    /// binds the next element, or unbinds `item` and `index` and leaves for
+    /// `to` when none is left.
+    pub fn run(&self, key: &str, name: &str, collect: &[String], context: &mut Context) -> Result<String> {
+        let (items, keyed) = match eval(&self.over, context)? {
+            Value::Array(items) => (items, self.keyed),
+            Value::Object(o) => {
+                let mut keys: Vec<_> = o.into_keys().collect();
+                keys.sort();
+                (keys.into_iter().map(Value::String).collect(), true)
+            }
+            value => {
+                return Err(Error::TypeError(format!(
+                    "foreach state '{}' can only iterate over an array or object, got {:?}",
+                    key, value
+                )))
+            }
+        };
+
+        let rounds = Rounds {
+            key,
+            body: &self.body,
+            to: &self.to,
+        };
+        let empty = if keyed {
+            Value::Object(HashMap::new())
+        } else {
+            Value::Array(Vec::new())
+        };
+        let index = match rounds.finished(context, name, empty) {
+            None => 0,
+            Some(round) => {
+                let value = gather(collect, context);
+                let gathered = match (context.get_context().get(name).cloned(), items.get(round)) {
+                    (Some(Value::Object(mut o)), Some(item)) => {
+                        o.insert(to_text(item), value);
+                        Value::Object(o)
+                    }
+                    (Some(Value::Array(mut a)), _) => {
+                        a.push(value);
+                        Value::Array(a)
+                    }
+                    (gathered, _) => gathered.unwrap_or(Value::Null),
+                };
+                context.set_variable(name.to_string(), gathered);
+                round + 1
+            }
+        };
+
+        match items.into_iter().nth(index) {
+            Some(item) => {
+                context.set_variable(self.item.clone(), item);
+                context.set_variable(self.index.clone(), Value::Number(index as f64));
+                Ok(rounds.next(context, index))
+            }
+            None => {
+                context.remove_variable(self.item.clone());
+                context.remove_variable(self.index.clone());
+                Ok(rounds.end(context))
+            }
+        }
+    }
+}
+
+/// Takes the variables of a finished round out of the context.
+fn gather(collect: &[String], context: &mut Context) -> Value {
+    let mut values: HashMap<String, Value> = collect
+        .iter()
+        .map(|name| (name.clone(), take(context, name)))
+        .collect();
+    match collect {
+        [name] => values.remove(name).unwrap_or(Value::Null),
+        _ => Value::Object(values),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn foreach(over: &str) -> Foreach {
+        Foreach {
+            over: parse(over).unwrap(),
+            item: "item".to_string(),
+            index: "index".to_string(),
+            body: "port".to_string(),
+            collect: None,
+            keyed: false,
+            to: "end".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_foreach_array() {
+        let foreach = foreach("$services");
+        let mut context = Context::new(HashMap::new());
+        let services = vec![Value::String("web".to_string()), Value::String("db".to_string())];
+        context.set_variable("services".to_string(), Value::Array(services));
+        let collect = ["port".to_string()];
+
+        let mut entered = Vec::new();
+        for port in [80.0, 5432.0] {
+            entered.push(foreach.run("ports", "ports", &collect, &mut context).unwrap());
+            assert_eq!(
+                context.get_context().get("index"),
+                Some(&Value::Number(entered.len() as f64 - 1.0))
+            );
+            context.set_variable("port".to_string(), Value::Number(port));
+        }
+        entered.push(foreach.run("ports", "ports", &collect, &mut context).unwrap());
+
+        assert_eq!(entered, vec!["port", "port", "end"]);
+        assert_eq!(
+            context.get_context().get("ports"),
+            Some(&Value::Array(vec![Value::Number(80.0), Value::Number(5432.0)]))
+        );
+        for name in ["item", "index", "port"] {
+            assert_eq!(context.get_context().get(name), None);
+        }
+        assert_eq!(context.loop_iteration("ports"), None);
+    }
+
+    #[test]
+    fn test_foreach_empty() {
+        let mut context = Context::new(HashMap::new());
+        context.set_variable("services".to_string(), Value::Null);
+        let next = foreach("$services").run("ports", "ports", &[], &mut context);
+        assert_eq!(
+            next,
+            Err(Error::TypeError(
+                "foreach state 'ports' can only iterate over an array or object, got Null".to_string()
+            ))
+        );
+
+        context.set_variable("services".to_string(), Value::Array(vec![]));
+        let next = foreach("$services").run("ports", "ports", &[], &mut context);
+        assert_eq!(next, Ok("end".to_string()));
+        assert_eq!(context.get_context().get("ports"), Some(&Value::Array(vec![])));
+
+        let mut keyed = foreach("$services");
+        keyed.keyed = true;
+        keyed.run("ports", "ports", &[], &mut context).unwrap();
+        assert_eq!(context.get_context().get("ports"), Some(&Value::Object(HashMap::new())));
+    }
+}
@@ -0,0 +1,455 @@
+use std::{collections::HashMap, fmt::Write, str::FromStr};
+
+use crate::{
+    error::{Error, Result},
+    parser::{Expr, Value},
+    state::{Node, State, Target},
+};
+
+/// Output format of [`render`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Dot,
+    Mermaid,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "dot" | "graphviz" => Ok(Format::Dot),
+            "mermaid" => Ok(Format::Mermaid),
+            s => Err(Error::ParseError(format!("Unknown graph format: {}", s))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shape {
+    Prompt,
+    Condition,
+    Goto,
+    Action,
+    Done,
+    /// A transition target that is not defined in the wizard.
+    Undefined,
+    /// Stands in for the state a dynamic `goto` resolves to at runtime.
+    Dynamic,
+}
+
+struct GraphNode {
+    key: String,
+    label: Vec<String>,
+    shape: Shape,
+}
+
+struct GraphEdge {
+    from: usize,
+    to: usize,
+    label: Option<String>,
+}
+
+struct Graph {
+    start: Option<usize>,
+    nodes: Vec<GraphNode>,
+    edges: Vec<GraphEdge>,
+}
+
+/// Renders the state graph of a loaded wizard as a flowchart.
+pub fn render(start: &str, nodes: &HashMap<String, Node>, format: Format) -> String {
+    let graph = build(start, nodes);
+    match format {
+        Format::Dot => dot(&graph),
+        Format::Mermaid => mermaid(&graph),
+    }
+}
+
+fn build(start: &str, nodes: &HashMap<String, Node>) -> Graph {
+    let mut keys: Vec<&String> = nodes.keys().collect();
+    keys.sort();
+
+    let mut graph = Graph {
+        start: None,
+        nodes: keys
+            .iter()
+            .map(|key| {
+                let (label, shape) = describe(key, &nodes[*key]);
+                GraphNode {
+                    key: key.to_string(),
+                    label,
+                    shape,
+                }
+            })
+            .collect(),
+        edges: Vec::new(),
+    };
+
+    for (from, key) in keys.iter().enumerate() {
+        for (label, target) in targets(&nodes[*key].state) {
+            let to = match target {
+                Some(target) => graph.node(&target, Shape::Undefined),
+                None => graph.add(GraphNode {
+                    key: graph.unique_key(&format!("{}.dynamic", key)),
+                    label: vec!["?".to_string()],
+                    shape: Shape::Dynamic,
+                }),
+            };
+            graph.edges.push(GraphEdge { from, to, label });
+        }
+    }
+
+    graph.start = Some(graph.node(start, Shape::Undefined));
+    graph
+}
+
+impl Graph {
+    fn add(&mut self, node: GraphNode) -> usize {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    /// `base`, or `base` with a number appended if a node already has that
+    /// key, so that made-up nodes never merge with a state in the output.
+    fn unique_key(&self, base: &str) -> String {
+        let taken = |key: &str| self.nodes.iter().any(|n| n.key == key);
+        let mut key = base.to_string();
+        let mut n = 1;
+        while taken(&key) {
+            n += 1;
+            key = format!("{}{}", base, n);
+        }
+        key
+    }
+
+    /// Looks a state up by key, adding it with `shape` if it does not exist.
+    fn node(&mut self, key: &str, shape: Shape) -> usize {
+        match self.nodes.iter().position(|n| n.key == key) {
+            Some(i) => i,
+            None => self.add(GraphNode {
+                key: key.to_string(),
+                label: vec![key.to_string(), "(undefined)".to_string()],
+                shape,
+            }),
+        }
+    }
+}
+
+fn describe(key: &str, node: &Node) -> (Vec<String>, Shape) {
+    match &node.state {
+        State::Prompt(prompt, _) => (
+            vec![
+                key.to_string(),
+                prompt.message().to_string(),
+                format!("{} → ${}", prompt.type_name(), node.name),
+            ],
+            Shape::Prompt,
+        ),
+        State::Condition(_) => (vec![key.to_string()], Shape::Condition),
+        State::Goto(_) => (vec![key.to_string(), "goto".to_string()], Shape::Goto),
+        State::Switch(_) => (vec![key.to_string()], Shape::Condition),
+        State::Set(_, _) => (
+            vec![key.to_string(), format!("set ${}", node.name)],
+            Shape::Action,
+        ),
+        State::Remove(_) => (
+            vec![key.to_string(), format!("remove ${}", node.name)],
+            Shape::Action,
+        ),
+        State::Call(call, _) => (
+            vec![
+                key.to_string(),
+                format!("call {} → ${}", call.wizard.display(), node.name),
+            ],
+            Shape::Action,
+        ),
+        State::Exec(exec) => (
+            vec![
+                key.to_string(),
+                exec.command
+                    .iter()
+                    .map(|arg| arg.source().replace('\n', "\\n"))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                format!("exec → ${}", node.name),
+            ],
+            Shape::Action,
+        ),
+        State::Review(review, _) => (
+            vec![key.to_string(), review.message.source().to_string(), "review".to_string()],
+            Shape::Prompt,
+        ),
+        State::Collect(_) => (
+            vec![key.to_string(), format!("collect → ${}", node.name)],
+            Shape::Action,
+        ),
+        State::Foreach(foreach) => (
+            vec![
+                key.to_string(),
+                format!("foreach ${} → ${}", foreach.item, node.name),
+            ],
+            Shape::Action,
+        ),
+        State::Done => (vec![key.to_string()], Shape::Done),
+    }
+}
+
+/// Outgoing edges of a state as `(label, target)`; a `None` target is only
+/// known at runtime.
+fn targets(state: &State) -> Vec<(Option<String>, Option<String>)> {
+    state
+        .targets()
+        .into_iter()
+        .map(|(label, target)| match target {
+            Target::Key(to) => (label, Some(to.clone())),
+            Target::Expr(Expr::Value(Value::String(s) | Value::Symbol(s))) => (label, Some(s.clone())),
+            // A dynamic goto has no branch name of its own.
+            Target::Expr(_) => (label.or_else(|| Some("dynamic".to_string())), None),
+        })
+        .collect()
+}
+
+fn dot(graph: &Graph) -> String {
+    let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+
+    let mut out = String::from("digraph wizard {\n    node [fontname=\"sans-serif\"];\n");
+    if let Some(start) = graph.start {
+        let marker = quote(&graph.unique_key("__start"));
+        let _ = writeln!(out, "    {} [shape=point];", marker);
+        let _ = writeln!(out, "    {} -> {};", marker, quote(&graph.nodes[start].key));
+    }
+
+    for node in &graph.nodes {
+        let attributes = match node.shape {
+            Shape::Prompt => "shape=box, style=rounded",
+            Shape::Condition => "shape=diamond",
+            Shape::Goto => "shape=cds",
+            Shape::Action => "shape=box",
+            Shape::Done => "shape=doublecircle",
+            Shape::Undefined => "shape=box, style=dashed, color=red",
+            Shape::Dynamic => "shape=circle, style=dashed",
+        };
+        let label = node
+            .label
+            .iter()
+            .map(|line| line.replace('\\', "\\\\").replace('"', "\\\""))
+            .collect::<Vec<_>>()
+            .join("\\n");
+        let _ = writeln!(
+            out,
+            "    {} [label=\"{}\", {}];",
+            quote(&node.key),
+            label,
+            attributes
+        );
+    }
+
+    for edge in &graph.edges {
+        let from = quote(&graph.nodes[edge.from].key);
+        let to = &graph.nodes[edge.to];
+        let mut attributes = Vec::new();
+        if let Some(label) = &edge.label {
+            attributes.push(format!("label={}", quote(label)));
+        }
+        if to.shape == Shape::Dynamic {
+            attributes.push("style=dashed".to_string());
+        }
+        if attributes.is_empty() {
+            let _ = writeln!(out, "    {} -> {};", from, quote(&to.key));
+        } else {
+            let _ = writeln!(out, "    {} -> {} [{}];", from, quote(&to.key), attributes.join(", "));
+        }
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+fn mermaid(graph: &Graph) -> String {
+    // Mermaid ids cannot contain arbitrary characters, so states are
+    // numbered and the key is shown in the label instead.
+    let id = |i: usize| format!("s{}", i);
+    let escape = |s: &str| s.replace('"', "#quot;");
+
+    let mut out = String::from("flowchart TD\n");
+    if let Some(start) = graph.start {
+        out.push_str("    start((( )))\n");
+        let _ = writeln!(out, "    start --> {}", id(start));
+    }
+
+    for (i, node) in graph.nodes.iter().enumerate() {
+        let label = node.label.iter().map(|l| escape(l)).collect::<Vec<_>>().join("<br/>");
+        let shape = match node.shape {
+            Shape::Prompt => format!("(\"{}\")", label),
+            Shape::Condition => format!("{{\"{}\"}}", label),
+            Shape::Goto => format!("[/\"{}\"/]", label),
+            Shape::Action => format!("[\"{}\"]", label),
+            Shape::Done => format!("([\"{}\"])", label),
+            Shape::Undefined => format!("[\"{}\"]", label),
+            Shape::Dynamic => format!("((\"{}\"))", label),
+        };
+        let _ = writeln!(out, "    {}{}", id(i), shape);
+    }
+
+    for edge in &graph.edges {
+        let arrow = if graph.nodes[edge.to].shape == Shape::Dynamic {
+            "-.->"
+        } else {
+            "-->"
+        };
+        match &edge.label {
+            Some(label) => {
+                let _ = writeln!(
+                    out,
+                    "    {} {}|\"{}\"| {}",
+                    id(edge.from),
+                    arrow,
+                    escape(label),
+                    id(edge.to)
+                );
+            }
+            None => {
+                let _ = writeln!(out, "    {} {} {}", id(edge.from), arrow, id(edge.to));
+            }
+        }
+    }
+
+    let done: Vec<String> = graph
+        .nodes
+        .iter()
+        .enumerate()
+        .filter(|(_, n)| n.shape == Shape::Done)
+        .map(|(i, _)| id(i))
+        .collect();
+    if !done.is_empty() {
+        out.push_str("    classDef done fill:#dfd,stroke:#393\n");
+        let _ = writeln!(out, "    class {} done", done.join(","));
+    }
+    let undefined: Vec<String> = graph
+        .nodes
+        .iter()
+        .enumerate()
+        .filter(|(_, n)| n.shape == Shape::Undefined)
+        .map(|(i, _)| id(i))
+        .collect();
+    if !undefined.is_empty() {
+        out.push_str("    classDef undefined stroke:#c33,stroke-dasharray:4\n");
+        let _ = writeln!(out, "    class {} undefined", undefined.join(","));
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::toml::load;
+
+    const WIZARD: &str = r#"
+        start = "name"
+
+        [state.name]
+        type = "text"
+        name = "name"
+        message = "What is your \"name\"?"
+        to = "check"
+
+        [state.check]
+        type = "condition"
+        condition = "$name == 'admin'"
+        branches = { true = "jump", false = "end" }
+
+        [state.jump]
+        type = "goto"
+        target = "$name"
+
+        [state.end]
+        type = "done"
+    "#;
+
+    #[test]
+    fn test_render_dot() {
+        let (start, nodes) = load(WIZARD).unwrap();
+        let dot = render(&start, &nodes, Format::Dot);
+
+        assert!(dot.starts_with("digraph wizard {\n"));
+        assert!(dot.contains("\"__start\" -> \"name\";"));
+        assert!(dot.contains(
+            "\"name\" [label=\"name\\nWhat is your \\\"name\\\"?\\ntext → $name\", shape=box, style=rounded];"
+        ));
+        assert!(dot.contains("\"check\" -> \"end\" [label=\"false\"];"));
+        assert!(dot.contains("\"check\" -> \"jump\" [label=\"true\"];"));
+        assert!(dot.contains("\"jump\" -> \"jump.dynamic\" [label=\"dynamic\", style=dashed];"));
+        assert!(dot.contains("\"end\" [label=\"end\", shape=doublecircle];"));
+    }
+
+    #[test]
+    fn test_render_mermaid() {
+        let (start, nodes) = load(WIZARD).unwrap();
+        let mermaid = render(&start, &nodes, Format::Mermaid);
+
+        // States are numbered in key order: check, end, jump, name.
+        assert_eq!(
+            mermaid,
+            [
+                "flowchart TD",
+                "    start((( )))",
+                "    start --> s3",
+                "    s0{\"check\"}",
+                "    s1([\"end\"])",
+                "    s2[/\"jump<br/>goto\"/]",
+                "    s3(\"name<br/>What is your #quot;name#quot;?<br/>text → $name\")",
+                "    s4((\"?\"))",
+                "    s0 -->|\"false\"| s1",
+                "    s0 -->|\"true\"| s2",
+                "    s2 -.->|\"dynamic\"| s4",
+                "    s3 --> s0",
+                "    classDef done fill:#dfd,stroke:#393",
+                "    class s1 done",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_render_undefined_target() {
+        let (start, nodes) = load(
+            r#"
+            start = "a"
+
+            [state.a]
+            type = "set"
+            name = "x"
+            value = "1"
+            to = "missing"
+        "#,
+        )
+        .unwrap();
+
+        let dot = render(&start, &nodes, Format::Dot);
+        assert!(dot.contains("\"missing\" [label=\"missing\\n(undefined)\", shape=box, style=dashed, color=red];"));
+    }
+
+    #[test]
+    fn test_render_made_up_keys() {
+        let (start, nodes) = load(
+            r#"
+            start = "__start"
+
+            [state.__start]
+            type = "goto"
+            target = "$next"
+
+            [state."__start.dynamic"]
+            type = "done"
+        "#,
+        )
+        .unwrap();
+
+        let dot = render(&start, &nodes, Format::Dot);
+        assert!(dot.contains("\"__start2\" -> \"__start\";"));
+        assert!(dot.contains("\"__start\" -> \"__start.dynamic2\" [label=\"dynamic\", style=dashed];"));
+        assert!(dot.contains("\"__start.dynamic\" [label=\"__start.dynamic\", shape=doublecircle];"));
+    }
+}
@@ -0,0 +1,145 @@
+use std::{fmt, str::FromStr};
+
+use promkit::crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+use crate::error::Error;
+
+/// A single key press such as `esc`, `ctrl+b` or `shift+tab`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBinding {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        Self { code, modifiers }
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        match event {
+            Event::Key(KeyEvent {
+                code,
+                modifiers,
+                kind: KeyEventKind::Press,
+                ..
+            }) => *code == self.code && *modifiers == self.modifiers,
+            _ => false,
+        }
+    }
+}
+
+impl Default for KeyBinding {
+    fn default() -> Self {
+        Self::new(KeyCode::Esc, KeyModifiers::NONE)
+    }
+}
+
+impl FromStr for KeyBinding {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        let mut parts: Vec<&str> = s.split('+').collect();
+        let key = parts
+            .pop()
+            .filter(|k| !k.is_empty())
+            .ok_or_else(|| Error::ParseError(format!("Invalid key binding: {}", s)))?;
+
+        let mut modifiers = KeyModifiers::NONE;
+        for part in parts {
+            modifiers |= match part {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(Error::ParseError(format!("Unknown modifier: {}", part))),
+            };
+        }
+
+        let code = match key {
+            "esc" | "escape" => KeyCode::Esc,
+            "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" => KeyCode::Backspace,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            k if k.starts_with('f') && k.len() > 1 => KeyCode::F(
+                k[1..]
+                    .parse()
+                    .map_err(|_| Error::ParseError(format!("Unknown key: {}", k)))?,
+            ),
+            k if k.chars().count() == 1 => KeyCode::Char(k.chars().next().unwrap()),
+            k => return Err(Error::ParseError(format!("Unknown key: {}", k))),
+        };
+
+        Ok(Self::new(code, modifiers))
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "ctrl"),
+            (KeyModifiers::ALT, "alt"),
+            (KeyModifiers::SHIFT, "shift"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{}+", name)?;
+            }
+        }
+
+        match self.code {
+            KeyCode::Esc => write!(f, "esc"),
+            KeyCode::Tab | KeyCode::BackTab => write!(f, "tab"),
+            KeyCode::Backspace => write!(f, "backspace"),
+            KeyCode::Left => write!(f, "left"),
+            KeyCode::Right => write!(f, "right"),
+            KeyCode::Up => write!(f, "up"),
+            KeyCode::Down => write!(f, "down"),
+            KeyCode::Home => write!(f, "home"),
+            KeyCode::End => write!(f, "end"),
+            KeyCode::PageUp => write!(f, "pageup"),
+            KeyCode::PageDown => write!(f, "pagedown"),
+            KeyCode::F(n) => write!(f, "f{}", n),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key_binding() {
+        assert_eq!("esc".parse(), Ok(KeyBinding::default()));
+        assert_eq!(
+            "ctrl+b".parse(),
+            Ok(KeyBinding::new(KeyCode::Char('b'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(
+            "Shift+Tab".parse(),
+            Ok(KeyBinding::new(KeyCode::BackTab, KeyModifiers::SHIFT))
+        );
+        assert_eq!(
+            "alt+left".parse(),
+            Ok(KeyBinding::new(KeyCode::Left, KeyModifiers::ALT))
+        );
+        assert!("hyper+x".parse::<KeyBinding>().is_err());
+        assert!("ctrl+".parse::<KeyBinding>().is_err());
+    }
+
+    #[test]
+    fn test_display_key_binding() {
+        let key: KeyBinding = "ctrl+shift+f5".parse().unwrap();
+        assert_eq!(key.to_string(), "ctrl+shift+f5");
+    }
+}
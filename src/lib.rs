@@ -5,5 +5,21 @@ pub mod error;
 pub mod state;
 mod condition;
 mod goto;
+mod switch;
+mod call;
+mod exec;
+mod collect;
+mod foreach;
+mod rounds;
+mod review;
 pub mod toml;
+pub mod check;
+pub mod key;
+pub mod answers;
+pub mod backend;
+pub mod scenario;
+pub mod graph;
+pub mod template;
+pub mod session;
+pub mod output;
 mod utils;
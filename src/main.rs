@@ -1,14 +1,13 @@
+use std::process::ExitCode;
+
 use anyhow::Ok;
 use app::App;
 use clap::Parser;
 
 mod app;
 
-fn main() -> anyhow::Result<()> {
+fn main() -> anyhow::Result<ExitCode> {
     let app = App::parse();
 
-    let context = app.run()?;
-    println!("{}", serde_json::to_string(&context)?);
-
-    Ok(())
+    Ok(app.run()?)
 }
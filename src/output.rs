@@ -0,0 +1,369 @@
+use std::{collections::HashMap, fmt::Write, str::FromStr};
+
+use crate::{
+    error::{Error, Result},
+    parser::Value,
+};
+
+/// Format the answers of a finished run are written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Pretty,
+    Yaml,
+    Toml,
+    /// `KEY=value` lines.
+    Dotenv,
+    /// `export KEY='value'` lines that can be passed to `eval`.
+    Shell,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(Format::Json),
+            "pretty" | "json-pretty" => Ok(Format::Pretty),
+            "yaml" | "yml" => Ok(Format::Yaml),
+            "toml" => Ok(Format::Toml),
+            "dotenv" | "env" => Ok(Format::Dotenv),
+            "shell" | "sh" => Ok(Format::Shell),
+            s => Err(Error::ParseError(format!("Unknown output format: {}", s))),
+        }
+    }
+}
+
+/// Joins the keys of nested values that `dotenv` and `shell` flatten. Only
+/// ASCII letters, digits and `_` are allowed, so that the joined keys stay
+/// valid shell identifiers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Separator(String);
+
+impl FromStr for Separator {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            Ok(Separator(s.to_string()))
+        } else {
+            Err(Error::ParseError(format!(
+                "Invalid separator: {} (only letters, digits and _ are allowed)",
+                s
+            )))
+        }
+    }
+}
+
+/// Renders the answers, one trailing newline included.
+pub fn render(answers: HashMap<String, Value>, format: Format, separator: &Separator) -> Result<String> {
+    match format {
+        Format::Dotenv => Ok(flatten(answers, separator)?
+            .into_iter()
+            .map(|(key, value)| format!("{}={}\n", key, dotenv_quote(&value)))
+            .collect()),
+        Format::Shell => Ok(flatten(answers, separator)?
+            .into_iter()
+            .map(|(key, value)| format!("export {}={}\n", key, shell_quote(&value)))
+            .collect()),
+        _ => {
+            let json = to_json(Value::Object(answers))?;
+            match format {
+                Format::Json => Ok(format!("{}\n", json)),
+                Format::Pretty => Ok(format!("{:#}\n", json)),
+                Format::Yaml => {
+                    let mut out = String::new();
+                    yaml(&json, 0, &mut out);
+                    Ok(out)
+                }
+                _ => toml::to_string(&without_nulls(json))
+                    .map_err(|e| Error::OutputError(e.to_string())),
+            }
+        }
+    }
+}
+
+pub fn to_json(value: Value) -> Result<serde_json::Value> {
+    Ok(match value {
+        Value::String(s) => serde_json::Value::String(s),
+        Value::Number(n) => serde_json::Number::from_f64(n)
+            .map(serde_json::Value::Number)
+            .ok_or_else(|| Error::OutputError(format!("{} is not a finite number", n)))?,
+        Value::Boolean(b) => serde_json::Value::Bool(b),
+        Value::Symbol(s) => serde_json::Value::String(s),
+        Value::Null => serde_json::Value::Null,
+        Value::Object(o) => {
+            // Sorted so that the output is stable across runs.
+            let mut entries: Vec<_> = o.into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            serde_json::Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| Ok((k, to_json(v)?)))
+                    .collect::<Result<_>>()?,
+            )
+        }
+        Value::Array(a) => serde_json::Value::Array(a.into_iter().map(to_json).collect::<Result<_>>()?),
+    })
+}
+
+/// TOML has no null, so null values are left out.
+fn without_nulls(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(o) => serde_json::Value::Object(
+            o.into_iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| (k, without_nulls(v)))
+                .collect(),
+        ),
+        serde_json::Value::Array(a) => serde_json::Value::Array(
+            a.into_iter()
+                .filter(|v| !v.is_null())
+                .map(without_nulls)
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+/// Flattens nested values into `(key, text)` pairs sorted by key, with
+/// array elements keyed by index. Keys are made valid shell identifiers,
+/// and two keys ending up the same is an error.
+fn flatten(answers: HashMap<String, Value>, separator: &Separator) -> Result<Vec<(String, String)>> {
+    fn walk(key: String, value: Value, separator: &str, out: &mut Vec<(String, String)>) {
+        let nested: Vec<(String, Value)> = match value {
+            Value::Object(o) if !o.is_empty() => o.into_iter().collect(),
+            Value::Array(a) if !a.is_empty() => a
+                .into_iter()
+                .enumerate()
+                .map(|(i, v)| (i.to_string(), v))
+                .collect(),
+            Value::String(s) | Value::Symbol(s) => return out.push((key, s)),
+            Value::Number(n) => return out.push((key, n.to_string())),
+            Value::Boolean(b) => return out.push((key, b.to_string())),
+            Value::Null | Value::Object(_) | Value::Array(_) => {
+                return out.push((key, String::new()))
+            }
+        };
+        for (k, v) in nested {
+            walk(format!("{}{}{}", key, separator, k), v, separator, out);
+        }
+    }
+
+    let mut out = Vec::new();
+    for (key, value) in answers {
+        walk(key, value, &separator.0, &mut out);
+    }
+
+    let mut out: Vec<_> = out
+        .into_iter()
+        .map(|(key, value)| (identifier(&key), value))
+        .collect();
+    out.sort();
+    if let Some(pair) = out.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+        return Err(Error::OutputError(format!(
+            "more than one answer is written as {}",
+            pair[0].0
+        )));
+    }
+    Ok(out)
+}
+
+fn identifier(key: &str) -> String {
+    let key: String = key
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    match key.chars().next() {
+        Some(c) if !c.is_ascii_digit() => key,
+        _ => format!("_{}", key),
+    }
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+fn dotenv_quote(value: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "_-./:@+,".contains(c);
+    if value.chars().all(plain) {
+        return value.to_string();
+    }
+
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' | '\\' | '$' | '`' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Block-style YAML. Strings are always double-quoted, which YAML reads the
+/// same way as JSON.
+fn yaml(value: &serde_json::Value, indent: usize, out: &mut String) {
+    let pad = "  ".repeat(indent);
+    match value {
+        serde_json::Value::Object(o) if !o.is_empty() => {
+            for (key, value) in o {
+                let _ = write!(out, "{}{}:", pad, yaml_key(key));
+                yaml_nested(value, indent, out);
+            }
+        }
+        serde_json::Value::Array(a) if !a.is_empty() => {
+            for value in a {
+                let _ = write!(out, "{}-", pad);
+                yaml_nested(value, indent, out);
+            }
+        }
+        value => {
+            let _ = writeln!(out, "{}{}", pad, yaml_scalar(value));
+        }
+    }
+}
+
+fn yaml_nested(value: &serde_json::Value, indent: usize, out: &mut String) {
+    match value {
+        serde_json::Value::Object(o) if !o.is_empty() => {
+            out.push('\n');
+            yaml(value, indent + 1, out);
+        }
+        serde_json::Value::Array(a) if !a.is_empty() => {
+            out.push('\n');
+            yaml(value, indent + 1, out);
+        }
+        value => {
+            let _ = writeln!(out, " {}", yaml_scalar(value));
+        }
+    }
+}
+
+fn yaml_scalar(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Object(_) => "{}".to_string(),
+        serde_json::Value::Array(_) => "[]".to_string(),
+        value => value.to_string(),
+    }
+}
+
+fn yaml_key(key: &str) -> String {
+    let plain = key.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        && !matches!(
+            key.to_ascii_lowercase().as_str(),
+            "true" | "false" | "null" | "yes" | "no" | "on" | "off" | "y" | "n"
+        );
+    if plain {
+        key.to_string()
+    } else {
+        serde_json::Value::String(key.to_string()).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn answers() -> HashMap<String, Value> {
+        HashMap::from([
+            ("name".to_string(), Value::String("O'Brien".to_string())),
+            ("age".to_string(), Value::Number(20.0)),
+            ("nickname".to_string(), Value::Null),
+            (
+                "server".to_string(),
+                Value::Object(HashMap::from([
+                    ("host".to_string(), Value::String("example.com".to_string())),
+                    (
+                        "tags".to_string(),
+                        Value::Array(vec![
+                            Value::String("a b".to_string()),
+                            Value::String("$HOME".to_string()),
+                        ]),
+                    ),
+                ])),
+            ),
+        ])
+    }
+
+    fn separator(s: &str) -> Separator {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_render_flat() {
+        assert_eq!(
+            render(answers(), Format::Shell, &separator("_")).unwrap(),
+            "export age='20'\n\
+             export name='O'\\''Brien'\n\
+             export nickname=''\n\
+             export server_host='example.com'\n\
+             export server_tags_0='a b'\n\
+             export server_tags_1='$HOME'\n"
+        );
+        assert_eq!(
+            render(answers(), Format::Dotenv, &separator("__")).unwrap(),
+            "age=20\n\
+             name=\"O'Brien\"\n\
+             nickname=\n\
+             server__host=example.com\n\
+             server__tags__0=\"a b\"\n\
+             server__tags__1=\"\\$HOME\"\n"
+        );
+    }
+
+    #[test]
+    fn test_render_structured() {
+        assert_eq!(
+            render(answers(), Format::Yaml, &separator("_")).unwrap(),
+            "age: 20.0\n\
+             name: \"O'Brien\"\n\
+             nickname: null\n\
+             server:\n  \
+               host: \"example.com\"\n  \
+               tags:\n    \
+                 - \"a b\"\n    \
+                 - \"$HOME\"\n"
+        );
+        assert_eq!(
+            render(answers(), Format::Toml, &separator("_")).unwrap(),
+            "age = 20.0\n\
+             name = \"O'Brien\"\n\
+             \n\
+             [server]\n\
+             host = \"example.com\"\n\
+             tags = [\"a b\", \"$HOME\"]\n"
+        );
+        assert_eq!(
+            render(HashMap::from([("n".to_string(), Value::Number(f64::NAN))]), Format::Json, &separator("_")),
+            Err(Error::OutputError("NaN is not a finite number".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_render_flat_errors() {
+        assert_eq!(
+            "-".parse::<Separator>(),
+            Err(Error::ParseError(
+                "Invalid separator: - (only letters, digits and _ are allowed)".to_string()
+            ))
+        );
+
+        let clashing = HashMap::from([
+            ("server_host".to_string(), Value::String("a".to_string())),
+            (
+                "server".to_string(),
+                Value::Object(HashMap::from([("host".to_string(), Value::String("b".to_string()))])),
+            ),
+        ]);
+        assert_eq!(
+            render(clashing, Format::Shell, &separator("_")),
+            Err(Error::OutputError("more than one answer is written as server_host".to_string()))
+        );
+    }
+}
@@ -3,17 +3,20 @@ use std::{cmp::Ordering, collections::HashMap};
 use nom::{
     branch::alt,
     bytes::complete::{tag, take_while1},
-    character::complete::{char, digit1},
-    combinator::{map, opt, recognize},
+    character::complete::{char, digit1, multispace0, satisfy},
+    combinator::{map, not, opt, recognize},
+    error::ErrorKind,
     multi::many0,
-    sequence::{delimited, pair, preceded},
+    sequence::{delimited, pair, preceded, terminated},
     IResult,
     multi::separated_list1,
 };
 
+use serde::{Deserialize, Serialize};
+
 use crate::error::{Error, Result};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Value {
     String(String),
     Number(f64),
@@ -21,78 +24,129 @@ pub enum Value {
     Symbol(String),
     Object(HashMap<String, Value>),
     Array(Vec<Value>),
+    Null,
 }
 
-impl PartialEq for Value {
-    fn eq(&self, other: &Self) -> bool {
+impl Value {
+    /// The name of the value's type, as used in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::Number(_) => "number",
+            Value::Boolean(_) => "boolean",
+            Value::Symbol(_) => "symbol",
+            Value::Object(_) => "object",
+            Value::Array(_) => "array",
+            Value::Null => "null",
+        }
+    }
+
+    /// Equality for `==` and `!=`, which applies the same coercions as
+    /// [`Value::compare`]: a string equals a number it parses as, and a
+    /// symbol equals a string with the same text. Arrays and objects are
+    /// compared element by element.
+    pub fn loosely_equals(&self, other: &Self) -> bool {
         match (self, other) {
-            (Self::String(l0), Self::String(r0)) => l0 == r0,
-            (Self::Number(l0), Self::Number(r0)) => l0 == r0,
-            (Self::Boolean(l0), Self::Boolean(r0)) => l0 == r0,
-            (Self::Symbol(l0), Self::Symbol(r0)) => l0 == r0,
-            (Self::Object(l0), Self::Object(r0)) => l0 == r0,
-            (Self::Array(l0), Self::Array(r0)) => l0 == r0,
-            (Self::String(l0), Self::Number(r0)) => l0 == &r0.to_string(),
-            (Self::Number(l0), Self::String(r0)) => &l0.to_string() == r0,
-            _ => false,
+            (Self::String(l) | Self::Symbol(l), Self::String(r) | Self::Symbol(r)) => l == r,
+            (Self::String(l), Self::Number(r)) => as_number(l) == Some(*r),
+            (Self::Number(l), Self::String(r)) => Some(*l) == as_number(r),
+            (Self::Array(l), Self::Array(r)) => {
+                l.len() == r.len() && l.iter().zip(r).all(|(l, r)| l.loosely_equals(r))
+            }
+            (Self::Object(l), Self::Object(r)) => {
+                l.len() == r.len()
+                    && l.iter().all(|(k, l)| r.get(k).is_some_and(|r| l.loosely_equals(r)))
+            }
+            (l, r) => l == r,
         }
     }
-}
 
-impl PartialOrd for Value {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    /// Orders two values for `<`, `<=`, `>` and `>=`.
+    ///
+    /// | left             | right            | ordering                                    |
+    /// |------------------|------------------|---------------------------------------------|
+    /// | number           | number           | numeric                                     |
+    /// | string or symbol | string or symbol | lexicographic on the text                   |
+    /// | string           | number           | numeric, if the string parses as a number   |
+    /// | boolean          | boolean          | `false < true`                              |
+    /// | array            | array            | element by element, then by length          |
+    /// | object           | object           | by sorted keys, then by value               |
+    ///
+    /// Any other pair, including anything with `null`, a string that is not
+    /// a number, or `NaN` is a type error naming both operand types.
+    pub fn compare(&self, other: &Self) -> Result<Ordering> {
+        let mismatch = || {
+            Error::TypeError(format!(
+                "Cannot compare {} with {}",
+                self.type_name(),
+                other.type_name()
+            ))
+        };
+
         match (self, other) {
-            (Self::String(l0), Self::String(r0)) => l0.partial_cmp(r0),
-            (Self::Number(l0), Self::Number(r0)) => l0.partial_cmp(r0),
-            (Self::Boolean(l0), Self::Boolean(r0)) => l0.partial_cmp(r0),
-            (Self::Symbol(l0), Self::Symbol(r0)) => l0.partial_cmp(r0),
-            (Self::Object(l0), Self::Object(r0)) => {
-                let l_keys: Vec<_> = l0.keys().collect();
-                let r_keys: Vec<_> = r0.keys().collect();
+            (Self::Number(l), Self::Number(r)) => l.partial_cmp(r).ok_or_else(mismatch),
+            (Self::String(l) | Self::Symbol(l), Self::String(r) | Self::Symbol(r)) => Ok(l.cmp(r)),
+            (Self::String(l), Self::Number(r)) => as_number(l)
+                .and_then(|l| l.partial_cmp(r))
+                .ok_or_else(mismatch),
+            (Self::Number(l), Self::String(r)) => as_number(r)
+                .and_then(|r| l.partial_cmp(&r))
+                .ok_or_else(mismatch),
+            (Self::Boolean(l), Self::Boolean(r)) => Ok(l.cmp(r)),
+            (Self::Array(l), Self::Array(r)) => {
+                for (l, r) in l.iter().zip(r.iter()) {
+                    match l.compare(r)? {
+                        Ordering::Equal => continue,
+                        ord => return Ok(ord),
+                    }
+                }
+                Ok(l.len().cmp(&r.len()))
+            }
+            (Self::Object(l), Self::Object(r)) => {
+                let mut l_keys: Vec<_> = l.keys().collect();
+                let mut r_keys: Vec<_> = r.keys().collect();
+                l_keys.sort();
+                r_keys.sort();
                 match l_keys.cmp(&r_keys) {
                     Ordering::Equal => {
                         for key in l_keys {
-                            match l0[key].partial_cmp(&r0[key]) {
-                                Some(Ordering::Equal) => continue,
-                                ord => return ord,
+                            match l[key].compare(&r[key])? {
+                                Ordering::Equal => continue,
+                                ord => return Ok(ord),
                             }
                         }
-                        Some(Ordering::Equal)
-                    }
-                    ord => Some(ord),
-                }
-            }
-            (Self::Array(l0), Self::Array(r0)) => {
-                for (l, r) in l0.iter().zip(r0.iter()) {
-                    match l.partial_cmp(r) {
-                        Some(Ordering::Equal) => continue,
-                        ord => return ord,
+                        Ok(Ordering::Equal)
                     }
+                    ord => Ok(ord),
                 }
-                Some(l0.len().cmp(&r0.len()))
             }
-            (Self::String(l0), Self::Number(r0)) => l0.parse::<f64>().unwrap().partial_cmp(r0),
-            (Self::Number(l0), Self::String(r0)) => l0.partial_cmp(&r0.parse::<f64>().unwrap()),
-            _ => None,
+            _ => Err(mismatch()),
         }
     }
+}
 
-    fn ge(&self, other: &Self) -> bool {
-        self.partial_cmp(other).unwrap() == Ordering::Greater
-            || self.partial_cmp(other).unwrap() == Ordering::Equal
-    }
-
-    fn gt(&self, other: &Self) -> bool {
-        self.partial_cmp(other).unwrap() == Ordering::Greater
-    }
+fn as_number(s: &str) -> Option<f64> {
+    s.trim().parse().ok()
+}
 
-    fn le(&self, other: &Self) -> bool {
-        self.partial_cmp(other).unwrap() == Ordering::Less
-            || self.partial_cmp(other).unwrap() == Ordering::Equal
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.compare(other).ok()
     }
+}
 
-    fn lt(&self, other: &Self) -> bool {
-        self.partial_cmp(other).unwrap() == Ordering::Less
+impl From<serde_json::Value> for Value {
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::String(s) => Value::String(s),
+            serde_json::Value::Number(n) => Value::Number(n.as_f64().unwrap()),
+            serde_json::Value::Bool(b) => Value::Boolean(b),
+            serde_json::Value::Object(o) => {
+                Value::Object(o.into_iter().map(|(k, v)| (k, Value::from(v))).collect())
+            }
+            serde_json::Value::Array(a) => Value::Array(a.into_iter().map(Value::from).collect()),
+            serde_json::Value::Null => Value::Null,
+        }
     }
 }
 
@@ -110,6 +164,8 @@ pub enum BinOp {
     Sub,
     Mul,
     Div,
+    And,
+    Or,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -154,18 +210,18 @@ fn symbol_expr(s: &str) -> Expr {
 
 fn parse_array(input: &str) -> IResult<&str, Expr> {
     let (input, _) = char('[')(input)?;
-    let (input, _) = nom::character::complete::multispace0(input)?;
+    let (input, _) = multispace0(input)?;
     
     let (input, elements) = opt(separated_list1(
         delimited(
-            nom::character::complete::multispace0,
+            multispace0,
             char(','),
-            nom::character::complete::multispace0
+            multispace0
         ),
-        parse_binary
+        parse_expr
     ))(input)?;
     
-    let (input, _) = nom::character::complete::multispace0(input)?;
+    let (input, _) = multispace0(input)?;
     let (input, _) = char(']')(input)?;
 
     let elements = elements.unwrap_or_default();
@@ -198,6 +254,11 @@ fn parse_value(input: &str) -> IResult<&str, Expr> {
             alt((map(tag("true"), |_| true), map(tag("false"), |_| false))),
             bool_expr,
         ),
+        // Null
+        map(
+            terminated(tag("null"), not(satisfy(|c| c.is_alphanumeric() || c == '_'))),
+            |_| Expr::Value(Value::Null),
+        ),
         // Symbol
         map(
             take_while1(|c: char| c.is_alphanumeric() || c == '_'),
@@ -208,18 +269,18 @@ fn parse_value(input: &str) -> IResult<&str, Expr> {
 
 fn parse_function(input: &str) -> IResult<&str, Expr> {
     let (input, name) = take_while1(|c: char| c.is_alphanumeric() || c == '_')(input)?;
-    let (input, _) = preceded(nom::character::complete::multispace0, char('('))(input)?;
+    let (input, _) = preceded(multispace0, char('('))(input)?;
     
     // 最初の引数をパース
-    let (input, first_arg) = preceded(nom::character::complete::multispace0, parse_binary)(input)?;
+    let (input, first_arg) = preceded(multispace0, parse_expr)(input)?;
     
     // 残りの引数をパース（カンマ区切り）
     let (input, args) = many0(|input| {
-        let (input, _) = preceded(nom::character::complete::multispace0, char(','))(input)?;
-        preceded(nom::character::complete::multispace0, parse_binary)(input)
+        let (input, _) = preceded(multispace0, char(','))(input)?;
+        preceded(multispace0, parse_expr)(input)
     })(input)?;
     
-    let (input, _) = preceded(nom::character::complete::multispace0, char(')'))(input)?;
+    let (input, _) = preceded(multispace0, char(')'))(input)?;
 
     // 全ての引数を1つの配列にまとめる
     let mut all_args = vec![first_arg];
@@ -234,162 +295,183 @@ fn parse_function(input: &str) -> IResult<&str, Expr> {
     ))
 }
 
-fn parse_unary(input: &str) -> IResult<&str, Expr> {
+fn parse_primary(input: &str) -> IResult<&str, Expr> {
     alt((
+        // 括弧で囲まれた式
+        delimited(
+            char('('),
+            preceded(multispace0, parse_expr),
+            preceded(multispace0, char(')')),
+        ),
         // Function expression
         parse_function,
-        // Dollar expression with optional dot or index access
+        // Dollar expression
+        map(preceded(char('$'), parse_value), |expr| Expr::UnaryOp {
+            op: UnaryOp::Dollar,
+            expr: Box::new(expr),
+        }),
+        parse_value,
+    ))(input)
+}
+
+fn parse_postfix(input: &str) -> IResult<&str, Expr> {
+    let (input, expr) = parse_primary(input)?;
+
+    let (input, accesses) = many0(alt((
+        // ドットアクセス
         map(
-            pair(
-                preceded(char('$'), parse_value),
-                many0(alt((
-                    // ドットアクセス
-                    map(
-                        preceded(
-                            preceded(nom::character::complete::multispace0, char('.')),
-                            parse_value,
-                        ),
-                        |field| (BinOp::Dot, field),
-                    ),
-                    // インデックスアクセス
-                    map(
-                        delimited(
-                            preceded(nom::character::complete::multispace0, char('[')),
-                            parse_binary,
-                            preceded(nom::character::complete::multispace0, char(']')),
-                        ),
-                        |expr| (BinOp::Index, expr),
-                    ),
-                ))),
+            preceded(preceded(multispace0, char('.')), parse_value),
+            |field| (BinOp::Dot, field),
+        ),
+        // インデックスアクセス
+        map(
+            delimited(
+                preceded(multispace0, char('[')),
+                preceded(multispace0, parse_expr),
+                preceded(multispace0, char(']')),
             ),
-            |(expr, accesses)| {
-                let mut result = Expr::UnaryOp {
-                    op: UnaryOp::Dollar,
-                    expr: Box::new(expr),
-                };
-
-                for (op, access_expr) in accesses {
-                    result = Expr::BinaryOp {
-                        op,
-                        left: Box::new(result),
-                        right: Box::new(access_expr),
-                    };
-                }
-
-                result
-            },
+            |expr| (BinOp::Index, expr),
         ),
+    )))(input)?;
+
+    Ok((input, fold(expr, accesses)))
+}
+
+fn parse_unary(input: &str) -> IResult<&str, Expr> {
+    alt((
         // Not expression
-        map(preceded(char('!'), parse_value), |expr| Expr::UnaryOp {
-            op: UnaryOp::Not,
-            expr: Box::new(expr),
+        map(preceded(pair(char('!'), multispace0), parse_unary), |expr| {
+            Expr::UnaryOp {
+                op: UnaryOp::Not,
+                expr: Box::new(expr),
+            }
         }),
-        parse_value,
+        parse_postfix,
     ))(input)
 }
 
-fn parse_arithmetic(input: &str) -> IResult<&str, Expr> {
-    let (input, first_term) = parse_term(input)?;
-    
+fn parse_multiplicative(input: &str) -> IResult<&str, Expr> {
+    let (input, first) = parse_unary(input)?;
+
     let (input, rest) = many0(pair(
         preceded(
-            nom::character::complete::multispace0,
-            alt((
-                map(char('+'), |_| BinOp::Add),
-                map(char('-'), |_| BinOp::Sub),
-            ))
+            multispace0,
+            alt((map(char('*'), |_| BinOp::Mul), map(char('/'), |_| BinOp::Div))),
         ),
-        preceded(nom::character::complete::multispace0, parse_term)
+        preceded(multispace0, parse_unary),
     ))(input)?;
-    
-    let result = rest.into_iter().fold(first_term, |acc, (op, term)| {
-        Expr::BinaryOp {
-            op,
-            left: Box::new(acc),
-            right: Box::new(term),
-        }
-    });
-    
-    Ok((input, result))
+
+    Ok((input, fold(first, rest)))
 }
 
-fn parse_term(input: &str) -> IResult<&str, Expr> {
-    let (input, first_factor) = parse_factor(input)?;
-    
+fn parse_additive(input: &str) -> IResult<&str, Expr> {
+    let (input, first) = parse_multiplicative(input)?;
+
     let (input, rest) = many0(pair(
         preceded(
-            nom::character::complete::multispace0,
-            alt((
-                map(char('*'), |_| BinOp::Mul),
-                map(char('/'), |_| BinOp::Div),
-            ))
+            multispace0,
+            alt((map(char('+'), |_| BinOp::Add), map(char('-'), |_| BinOp::Sub))),
         ),
-        preceded(nom::character::complete::multispace0, parse_factor)
+        preceded(multispace0, parse_multiplicative),
     ))(input)?;
-    
-    let result = rest.into_iter().fold(first_factor, |acc, (op, factor)| {
-        Expr::BinaryOp {
-            op,
-            left: Box::new(acc),
-            right: Box::new(factor),
-        }
-    });
-    
-    Ok((input, result))
+
+    Ok((input, fold(first, rest)))
 }
 
-fn parse_factor(input: &str) -> IResult<&str, Expr> {
+fn comparison_op(input: &str) -> IResult<&str, BinOp> {
     alt((
-        // 括弧で囲まれた式
-        delimited(
-            char('('),
-            preceded(
-                nom::character::complete::multispace0,
-                parse_binary
-            ),
-            preceded(nom::character::complete::multispace0, char(')'))
-        ),
-        parse_unary
+        map(tag(">="), |_| BinOp::Ge),
+        map(tag(">"), |_| BinOp::Gt),
+        map(tag("<="), |_| BinOp::Le),
+        map(tag("<"), |_| BinOp::Lt),
     ))(input)
 }
 
-fn parse_binary(input: &str) -> IResult<&str, Expr> {
-    let (input, left) = parse_arithmetic(input)?;
-
-    let (input, op) = opt(preceded(
-        nom::character::complete::multispace0,
-        alt((
-            map(tag("=="), |_| BinOp::Eq),
-            map(tag("!="), |_| BinOp::NotEq),
-            map(tag(">="), |_| BinOp::Ge),
-            map(tag(">"), |_| BinOp::Gt),
-            map(tag("<="), |_| BinOp::Le),
-            map(tag("<"), |_| BinOp::Lt),
-            map(tag("."), |_| BinOp::Dot),
-        )),
+fn equality_op(input: &str) -> IResult<&str, BinOp> {
+    alt((
+        map(tag("=="), |_| BinOp::Eq),
+        map(tag("!="), |_| BinOp::NotEq),
+    ))(input)
+}
+
+/// Parses `operand (op operand)?`. Comparisons do not associate, so a
+/// second operator of the same level is a hard failure rather than
+/// trailing input.
+fn non_associative(
+    input: &str,
+    operand: fn(&str) -> IResult<&str, Expr>,
+    op: fn(&str) -> IResult<&str, BinOp>,
+) -> IResult<&str, Expr> {
+    let (input, left) = operand(input)?;
+    let (input, right) = opt(pair(
+        preceded(multispace0, op),
+        preceded(multispace0, operand),
     ))(input)?;
 
-    match op {
-        Some(op) => {
-            let (input, right) =
-                preceded(nom::character::complete::multispace0, parse_arithmetic)(input)?;
-            Ok((
-                input,
-                Expr::BinaryOp {
-                    op,
-                    left: Box::new(left),
-                    right: Box::new(right),
-                },
-            ))
-        }
-        None => Ok((input, left)),
+    let Some((op_kind, right)) = right else {
+        return Ok((input, left));
+    };
+
+    if preceded(multispace0, op)(input).is_ok() {
+        return Err(nom::Err::Failure(nom::error::Error::new(
+            input,
+            ErrorKind::Verify,
+        )));
     }
+
+    Ok((input, fold(left, vec![(op_kind, right)])))
+}
+
+fn parse_comparison(input: &str) -> IResult<&str, Expr> {
+    non_associative(input, parse_additive, comparison_op)
+}
+
+fn parse_equality(input: &str) -> IResult<&str, Expr> {
+    non_associative(input, parse_comparison, equality_op)
+}
+
+fn parse_and(input: &str) -> IResult<&str, Expr> {
+    let (input, first) = parse_equality(input)?;
+
+    let (input, rest) = many0(pair(
+        preceded(multispace0, map(tag("&&"), |_| BinOp::And)),
+        preceded(multispace0, parse_equality),
+    ))(input)?;
+
+    Ok((input, fold(first, rest)))
+}
+
+fn parse_or(input: &str) -> IResult<&str, Expr> {
+    let (input, first) = parse_and(input)?;
+
+    let (input, rest) = many0(pair(
+        preceded(multispace0, map(tag("||"), |_| BinOp::Or)),
+        preceded(multispace0, parse_and),
+    ))(input)?;
+
+    Ok((input, fold(first, rest)))
+}
+
+fn parse_expr(input: &str) -> IResult<&str, Expr> {
+    parse_or(input)
+}
+
+/// Builds a left-associative chain of binary operations.
+fn fold(first: Expr, rest: Vec<(BinOp, Expr)>) -> Expr {
+    rest.into_iter().fold(first, |left, (op, right)| Expr::BinaryOp {
+        op,
+        left: Box::new(left),
+        right: Box::new(right),
+    })
 }
 
 pub fn parse(input: &str) -> Result<Expr> {
-    match parse_binary(input.trim()) {
+    match parse_expr(input.trim()) {
         Ok(("", expr)) => Ok(expr),
         Ok((remain, _)) => Err(Error::TrailingInput(remain.to_string())),
+        Err(nom::Err::Failure(e)) if e.code == ErrorKind::Verify => {
+            Err(Error::ChainedComparison(input.trim().to_string()))
+        }
         Err(e) => Err(Error::ParseError(e.to_string())),
     }
 }
@@ -424,6 +506,15 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_null() {
+        assert_eq!(parse("null"), Ok(Expr::Value(Value::Null)));
+        assert_eq!(
+            parse("nullable"),
+            Ok(Expr::Value(Value::Symbol("nullable".to_string())))
+        );
+    }
+
     #[test]
     fn test_parse_dollar() {
         assert_eq!(
@@ -757,4 +848,131 @@ mod tests {
             })
         );
     }
+
+    fn var(name: &str) -> Expr {
+        Expr::UnaryOp {
+            op: UnaryOp::Dollar,
+            expr: Box::new(Expr::Value(Value::Symbol(name.to_string()))),
+        }
+    }
+
+    fn num(n: f64) -> Expr {
+        Expr::Value(Value::Number(n))
+    }
+
+    fn bin(op: BinOp, left: Expr, right: Expr) -> Expr {
+        Expr::BinaryOp {
+            op,
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    #[test]
+    fn test_parse_logical() {
+        assert_eq!(
+            parse("$age > 18 && $country == 'JP'"),
+            Ok(bin(
+                BinOp::And,
+                bin(BinOp::Gt, var("age"), num(18.0)),
+                bin(
+                    BinOp::Eq,
+                    var("country"),
+                    Expr::Value(Value::String("JP".to_string()))
+                ),
+            ))
+        );
+
+        // && は || より強く結合する
+        assert_eq!(
+            parse("$a || $b && $c"),
+            Ok(bin(BinOp::Or, var("a"), bin(BinOp::And, var("b"), var("c"))))
+        );
+
+        // 同じ優先順位は左結合
+        assert_eq!(
+            parse("$a || $b || $c"),
+            Ok(bin(BinOp::Or, bin(BinOp::Or, var("a"), var("b")), var("c")))
+        );
+
+        assert_eq!(
+            parse("($a || $b) && $c"),
+            Ok(bin(BinOp::And, bin(BinOp::Or, var("a"), var("b")), var("c")))
+        );
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        // unary > multiplicative > additive > comparison > equality > and > or
+        assert_eq!(
+            parse("!$done || 1 + 2 * 3 > 4 == true && $x"),
+            Ok(bin(
+                BinOp::Or,
+                Expr::UnaryOp {
+                    op: UnaryOp::Not,
+                    expr: Box::new(var("done")),
+                },
+                bin(
+                    BinOp::And,
+                    bin(
+                        BinOp::Eq,
+                        bin(
+                            BinOp::Gt,
+                            bin(
+                                BinOp::Add,
+                                num(1.0),
+                                bin(BinOp::Mul, num(2.0), num(3.0))
+                            ),
+                            num(4.0),
+                        ),
+                        Expr::Value(Value::Boolean(true)),
+                    ),
+                    var("x"),
+                ),
+            ))
+        );
+
+        assert_eq!(
+            parse("$a < $b != $c"),
+            Ok(bin(BinOp::NotEq, bin(BinOp::Lt, var("a"), var("b")), var("c")))
+        );
+    }
+
+    #[test]
+    fn test_parse_not() {
+        assert_eq!(
+            parse("!!$flag.enabled"),
+            Ok(Expr::UnaryOp {
+                op: UnaryOp::Not,
+                expr: Box::new(Expr::UnaryOp {
+                    op: UnaryOp::Not,
+                    expr: Box::new(bin(
+                        BinOp::Dot,
+                        var("flag"),
+                        Expr::Value(Value::Symbol("enabled".to_string()))
+                    )),
+                }),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_chained_comparison() {
+        assert_eq!(
+            parse("1 < $x < 10"),
+            Err(Error::ChainedComparison("1 < $x < 10".to_string()))
+        );
+        assert_eq!(
+            parse("$a == $b == $c"),
+            Err(Error::ChainedComparison("$a == $b == $c".to_string()))
+        );
+        assert_eq!(
+            parse("$a && ($b >= 1 > 0)"),
+            Err(Error::ChainedComparison("$a && ($b >= 1 > 0)".to_string()))
+        );
+        assert_eq!(
+            parse("$a && $b )"),
+            Err(Error::TrailingInput(" )".to_string()))
+        );
+    }
 }
@@ -1,10 +1,27 @@
-use crate::{error::{Error, Result}, eval::{eval, Context}, parser::{Expr, Value}, utils::get_options};
-use promkit::preset::{
-    checkbox::Checkbox, confirm::Confirm, listbox::Listbox, password::Password, query_selector::QuerySelector, readline::Readline
+use crate::{
+    backend::{Item, PromptBackend, Request, Response},
+    error::{Error, Result},
+    eval::{eval, Context},
+    parser::{Expr, Value},
+    template::{to_text, Template},
+    utils::get_options,
 };
 
+/// What the state machine should do after a prompt has finished.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Continue,
+    Back,
+}
+
 pub trait Prompt {
-    fn run(&self, name: &str, context: &mut Context) -> Result<()>;
+    fn run(
+        &self,
+        key: &str,
+        name: &str,
+        context: &mut Context,
+        backend: &mut dyn PromptBackend,
+    ) -> Result<Flow>;
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -12,153 +29,630 @@ pub enum PromptType {
     Text(TextPrompt),
     Confirm(ConfirmPrompt),
     Password(PasswordPrompt),
+    Number(NumberPrompt),
+    Integer(IntegerPrompt),
     Select(SelectPrompt),
     MultiSelect(MultiSelectPrompt),
     FuzzySelect(FuzzySelectPrompt),
 }
 
+impl PromptType {
+    /// The `type` this prompt is declared with in the wizard file.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            PromptType::Text(_) => "text",
+            PromptType::Confirm(_) => "confirm",
+            PromptType::Password(_) => "password",
+            PromptType::Number(_) => "number",
+            PromptType::Integer(_) => "integer",
+            PromptType::Select(_) => "select",
+            PromptType::MultiSelect(_) => "multi_select",
+            PromptType::FuzzySelect(_) => "fuzzy_select",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        self.template().source()
+    }
+
+    /// The message as shown when the prompt runs in `context`.
+    pub fn render_message(&self, context: &Context) -> Result<String> {
+        self.template().render(context)
+    }
+
+    fn template(&self) -> &Template {
+        match self {
+            PromptType::Text(prompt) => &prompt.message,
+            PromptType::Confirm(prompt) => &prompt.message,
+            PromptType::Password(prompt) => &prompt.message,
+            PromptType::Number(prompt) => &prompt.message,
+            PromptType::Integer(prompt) => &prompt.message,
+            PromptType::Select(prompt) => &prompt.message,
+            PromptType::MultiSelect(prompt) => &prompt.message,
+            PromptType::FuzzySelect(prompt) => &prompt.message,
+        }
+    }
+
+    /// Whether the prompt is asked in `context`. A prompt without `when`
+    /// always is.
+    pub fn applies(&self, context: &Context) -> Result<bool> {
+        let Some(when) = self.guard() else {
+            return Ok(true);
+        };
+        match eval(when, context)? {
+            Value::Boolean(b) => Ok(b),
+            _ => Err(Error::TypeError("when must evaluate to a boolean".to_string())),
+        }
+    }
+
+    /// The value a skipped prompt assigns: its `default`, if any, turned
+    /// into the value answering with it would store.
+    pub fn skipped_value(&self, context: &Context) -> Result<Option<Value>> {
+        match self {
+            PromptType::Text(prompt) => {
+                let accept = accept_text(prompt.validation.as_ref());
+                accept_default(default_text(&prompt.default, context)?, context, accept)
+            }
+            PromptType::Password(prompt) => {
+                let accept = accept_text(prompt.validation.as_ref());
+                accept_default(default_text(&prompt.default, context)?, context, accept)
+            }
+            PromptType::Confirm(prompt) => Ok(default_confirm(&prompt.default, context)?.map(Value::Boolean)),
+            PromptType::Number(prompt) => {
+                let accept = accept_number(prompt.bounds.eval(context)?, false);
+                accept_default(default_text(&prompt.default, context)?, context, accept)
+            }
+            PromptType::Integer(prompt) => {
+                let accept = accept_number(prompt.bounds.eval(context)?, true);
+                accept_default(default_text(&prompt.default, context)?, context, accept)
+            }
+            PromptType::Select(SelectPrompt { options, default, .. })
+            | PromptType::FuzzySelect(FuzzySelectPrompt { options, default, .. }) => {
+                let mut opts = eval_options(options, context)?;
+                Ok(default_index(default, context, &opts)?.map(|i| opts.swap_remove(i).value))
+            }
+            PromptType::MultiSelect(prompt) => {
+                let opts = eval_options(&prompt.options, context)?;
+                Ok(default_indexes(&prompt.default, context, &opts)?
+                    .map(|picked| Value::Array(picked.into_iter().map(|i| opts[i].value.clone()).collect())))
+            }
+        }
+    }
+
+    fn guard(&self) -> Option<&Expr> {
+        match self {
+            PromptType::Text(prompt) => prompt.when.as_ref(),
+            PromptType::Confirm(prompt) => prompt.when.as_ref(),
+            PromptType::Password(prompt) => prompt.when.as_ref(),
+            PromptType::Number(prompt) => prompt.when.as_ref(),
+            PromptType::Integer(prompt) => prompt.when.as_ref(),
+            PromptType::Select(prompt) => prompt.when.as_ref(),
+            PromptType::MultiSelect(prompt) => prompt.when.as_ref(),
+            PromptType::FuzzySelect(prompt) => prompt.when.as_ref(),
+        }
+    }
+
+    /// Every expression the prompt evaluates when it runs.
+    pub fn expressions(&self) -> Vec<&Expr> {
+        let (message, options, default, validation, bounds): (_, &[Choice], _, _, _) = match self {
+            PromptType::Text(p) => (&p.message, &[], &p.default, p.validation.as_ref(), None),
+            PromptType::Confirm(p) => (&p.message, &[], &p.default, None, None),
+            PromptType::Password(p) => (&p.message, &[], &p.default, p.validation.as_ref(), None),
+            PromptType::Number(p) => (&p.message, &[], &p.default, None, Some(&p.bounds)),
+            PromptType::Integer(p) => (&p.message, &[], &p.default, None, Some(&p.bounds)),
+            PromptType::Select(p) => (&p.message, &p.options, &p.default, None, None),
+            PromptType::MultiSelect(p) => (&p.message, &p.options, &p.default, None, None),
+            PromptType::FuzzySelect(p) => (&p.message, &p.options, &p.default, None, None),
+        };
+
+        message
+            .expressions()
+            .chain(options.iter().flat_map(|option| match option {
+                Choice::Expr(expr) => vec![expr],
+                Choice::Label(label) => label.expressions().collect(),
+                Choice::Item {
+                    label,
+                    value,
+                    description,
+                } => label
+                    .expressions()
+                    .chain(value)
+                    .chain(description.iter().flat_map(|d| d.expressions()))
+                    .collect(),
+                Choice::From { items, label } => std::iter::once(items).chain(label).collect(),
+            }))
+            .chain(default)
+            .chain(self.guard())
+            .chain(validation.into_iter().flat_map(|v| {
+                std::iter::once(&v.expr).chain(v.error_message.iter().flat_map(|m| m.expressions()))
+            }))
+            .chain(bounds.into_iter().flat_map(|b| [&b.min, &b.max, &b.step].into_iter().flatten()))
+            .collect()
+    }
+}
+
 impl Prompt for PromptType {
-    fn run(&self, name: &str, context: &mut Context) -> Result<()> {
+    fn run(&self, key: &str, name: &str, context: &mut Context, backend: &mut dyn PromptBackend) -> Result<Flow> {
         match self {
-            PromptType::Text(prompt) => prompt.run(name, context),
-            PromptType::Confirm(prompt) => prompt.run(name, context),
-            PromptType::Password(prompt) => prompt.run(name, context),
-            PromptType::Select(prompt) => prompt.run(name, context),
-            PromptType::MultiSelect(prompt) => prompt.run(name, context),
-            PromptType::FuzzySelect(prompt) => prompt.run(name, context),
+            PromptType::Text(prompt) => prompt.run(key, name, context, backend),
+            PromptType::Confirm(prompt) => prompt.run(key, name, context, backend),
+            PromptType::Password(prompt) => prompt.run(key, name, context, backend),
+            PromptType::Number(prompt) => prompt.run(key, name, context, backend),
+            PromptType::Integer(prompt) => prompt.run(key, name, context, backend),
+            PromptType::Select(prompt) => prompt.run(key, name, context, backend),
+            PromptType::MultiSelect(prompt) => prompt.run(key, name, context, backend),
+            PromptType::FuzzySelect(prompt) => prompt.run(key, name, context, backend),
+        }
+    }
+}
+
+fn store<T>(response: Response<T>, name: &str, context: &mut Context, to_value: impl FnOnce(T) -> Value) -> Flow {
+    match response {
+        Response::Submit(result) => {
+            context.set_variable(name.to_string(), to_value(result));
+            Flow::Continue
+        }
+        Response::Back => Flow::Back,
+    }
+}
+
+/// An entry of a select-type prompt's `options`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Choice {
+    /// An expression; arrays and objects expand into several options.
+    Expr(Expr),
+    /// A label with `${...}` placeholders, rendered into one option.
+    Label(Template),
+    /// A label shown in place of the value that is stored when the option
+    /// is picked; the rendered label itself when there is no value.
+    Item {
+        label: Template,
+        value: Option<Expr>,
+        description: Option<Template>,
+    },
+    /// One option per element of the array `items` evaluates to, storing
+    /// the element itself. `label` is evaluated with the element bound to
+    /// `$_`; a bare name is shorthand for that field of the element.
+    From { items: Expr, label: Option<Expr> },
+}
+
+fn eval_options(options: &[Choice], context: &Context) -> Result<Vec<Item>> {
+    let mut opts = Vec::new();
+    for option in options {
+        match option {
+            Choice::Expr(expr) => {
+                opts.extend(get_options(vec![eval(expr, context)?]).into_iter().map(Item::new))
+            }
+            Choice::Label(label) => opts.push(Item::new(label.render(context)?)),
+            Choice::Item {
+                label,
+                value,
+                description,
+            } => {
+                let label = label.render(context)?;
+                opts.push(Item {
+                    value: match value {
+                        Some(value) => eval(value, context)?,
+                        None => Value::String(label.clone()),
+                    },
+                    description: description.as_ref().map(|d| d.render(context)).transpose()?,
+                    label,
+                });
+            }
+            Choice::From { items, label } => {
+                let items = match eval(items, context)? {
+                    Value::Array(items) => items,
+                    Value::Null => vec![],
+                    value => {
+                        return Err(Error::TypeError(format!(
+                            "options_from must evaluate to an array, got {}",
+                            value.type_name()
+                        )))
+                    }
+                };
+                for item in items {
+                    let label = match label {
+                        Some(label) => to_text(&eval_label(label, &item, context)?),
+                        None => to_text(&item),
+                    };
+                    opts.push(Item {
+                        label,
+                        description: None,
+                        value: item,
+                    });
+                }
+            }
+        }
+    }
+    Ok(opts)
+}
+
+fn eval_label(label: &Expr, item: &Value, context: &Context) -> Result<Value> {
+    if let (Expr::Value(Value::Symbol(field)), Value::Object(fields)) = (label, item) {
+        return fields
+            .get(field)
+            .cloned()
+            .ok_or_else(|| Error::UndefinedVariable(format!("$_.{}", field)));
+    }
+
+    let mut context = context.clone();
+    context.set_variable(ANSWER_VARIABLE.to_string(), item.clone());
+    eval(label, &context)
+}
+
+/// Variable the candidate answer is bound to while `validate` runs, and
+/// each element while the `label` of `options_from` is evaluated.
+pub const ANSWER_VARIABLE: &str = "_";
+
+/// A `validate` expression and the message shown when it rejects an answer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Validation {
+    pub expr: Expr,
+    pub error_message: Option<Template>,
+}
+
+impl Validation {
+    /// Checks `answer`, bound to `$_`, and returns why it was rejected.
+    pub fn check(&self, answer: &Value, context: &Context) -> Result<Option<String>> {
+        let mut context = context.clone();
+        context.set_variable(ANSWER_VARIABLE.to_string(), answer.clone());
+
+        match eval(&self.expr, &context)? {
+            Value::Boolean(true) => Ok(None),
+            Value::Boolean(false) => match &self.error_message {
+                Some(message) => message.render(&context).map(Some),
+                None => Ok(Some("Invalid input".to_string())),
+            },
+            _ => Err(Error::TypeError(
+                "Validation must evaluate to a boolean".to_string(),
+            )),
+        }
+    }
+}
+
+/// A typed answer, or why the answer was rejected.
+type Accepted = std::result::Result<Value, String>;
+
+/// Asks for text until `accept` turns it into a value. A rejected answer is
+/// asked again with the reason in `Request::error` and the answer as the
+/// default.
+fn ask_until_accepted(
+    request: Request,
+    mut default: Option<String>,
+    context: &mut Context,
+    mut ask: impl FnMut(&Request, Option<&str>) -> Result<Response<String>>,
+    accept: impl Fn(&str, &Context) -> Result<Accepted>,
+) -> Result<Flow> {
+    let mut error = None;
+    loop {
+        let attempt = Request { error: error.as_deref(), ..request };
+        let Response::Submit(answer) = ask(&attempt, default.as_deref())? else {
+            return Ok(Flow::Back);
+        };
+
+        match accept(&answer, context)? {
+            Ok(value) => {
+                context.set_variable(request.name.to_string(), value);
+                return Ok(Flow::Continue);
+            }
+            Err(reason) => {
+                error = Some(reason);
+                default = Some(answer);
+            }
+        }
+    }
+}
+
+/// Turns the default of a skipped prompt into a value the way an answer is,
+/// failing where an answer would be asked again.
+fn accept_default(
+    default: Option<String>,
+    context: &Context,
+    accept: impl Fn(&str, &Context) -> Result<Accepted>,
+) -> Result<Option<Value>> {
+    let Some(default) = default else {
+        return Ok(None);
+    };
+    match accept(&default, context)? {
+        Ok(value) => Ok(Some(value)),
+        Err(reason) => Err(Error::TypeError(format!(
+            "Default '{}' of a skipped prompt is rejected: {}",
+            default, reason
+        ))),
+    }
+}
+
+fn accept_text(validation: Option<&Validation>) -> impl Fn(&str, &Context) -> Result<Accepted> + '_ {
+    move |answer, context| {
+        let value = Value::String(answer.to_string());
+        match validation {
+            Some(validation) => Ok(match validation.check(&value, context)? {
+                Some(reason) => Err(reason),
+                None => Ok(value),
+            }),
+            None => Ok(Ok(value)),
+        }
+    }
+}
+
+/// The `min`, `max` and `step` of a number or integer prompt.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Bounds {
+    pub min: Option<Expr>,
+    pub max: Option<Expr>,
+    /// Answers must be `min` (or 0) plus a multiple of `step`.
+    pub step: Option<Expr>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Range {
+    min: Option<f64>,
+    max: Option<f64>,
+    step: Option<f64>,
+}
+
+impl Bounds {
+    fn eval(&self, context: &Context) -> Result<Range> {
+        let bound = |expr: &Option<Expr>, name: &str| -> Result<Option<f64>> {
+            let Some(expr) = expr else {
+                return Ok(None);
+            };
+            match eval(expr, context)? {
+                Value::Number(n) => Ok(Some(n)),
+                _ => Err(Error::TypeError(format!("{} must be a number", name))),
+            }
+        };
+
+        let range = Range {
+            min: bound(&self.min, "min")?,
+            max: bound(&self.max, "max")?,
+            step: bound(&self.step, "step")?,
+        };
+        if range.step.is_some_and(|step| step <= 0.0) {
+            return Err(Error::TypeError("step must be greater than 0".to_string()));
+        }
+        Ok(range)
+    }
+}
+
+fn accept_number(range: Range, integer: bool) -> impl Fn(&str, &Context) -> Result<Accepted> {
+    move |answer, _| {
+        let n = match answer.trim().parse::<f64>() {
+            Ok(n) if n.is_finite() && (!integer || n.fract() == 0.0) => n,
+            _ if integer => return Ok(Err(format!("'{}' is not an integer", answer))),
+            _ => return Ok(Err(format!("'{}' is not a number", answer))),
+        };
+
+        if let Some(min) = range.min.filter(|min| n < *min) {
+            return Ok(Err(format!("Must be at least {}", min)));
         }
+        if let Some(max) = range.max.filter(|max| n > *max) {
+            return Ok(Err(format!("Must be at most {}", max)));
+        }
+        if let Some(step) = range.step {
+            let steps = (n - range.min.unwrap_or(0.0)) / step;
+            if (steps - steps.round()).abs() > 1e-9 {
+                return Ok(Err(match range.min {
+                    Some(min) => format!("Must be {} plus a multiple of {}", min, step),
+                    None => format!("Must be a multiple of {}", step),
+                }));
+            }
+        }
+        Ok(Ok(Value::Number(n)))
     }
 }
 
+/// Evaluates a text or password prompt's `default` into its initial input.
+/// A `null` default is no default, so optional arguments can be used as one.
+fn default_text(default: &Option<Expr>, context: &Context) -> Result<Option<String>> {
+    let Some(expr) = default else {
+        return Ok(None);
+    };
+    match eval(expr, context)? {
+        Value::Null => Ok(None),
+        Value::String(s) | Value::Symbol(s) => Ok(Some(s)),
+        Value::Number(n) => Ok(Some(n.to_string())),
+        Value::Boolean(b) => Ok(Some(b.to_string())),
+        _ => Err(Error::TypeError(
+            "Default of a text prompt must be a string".to_string(),
+        )),
+    }
+}
+
+fn default_confirm(default: &Option<Expr>, context: &Context) -> Result<Option<bool>> {
+    let Some(expr) = default else {
+        return Ok(None);
+    };
+    match eval(expr, context)? {
+        Value::Null => Ok(None),
+        Value::Boolean(b) => Ok(Some(b)),
+        _ => Err(Error::TypeError(
+            "Default of a confirm prompt must be a boolean".to_string(),
+        )),
+    }
+}
+
+/// Evaluates a select-type prompt's `default` into the indexes of the
+/// options it names by value or label. An array that is not itself an
+/// option's value names several options; values that are not among the
+/// options are ignored. A `null` default is no default.
+fn default_indexes(default: &Option<Expr>, context: &Context, opts: &[Item]) -> Result<Option<Vec<usize>>> {
+    let Some(expr) = default else {
+        return Ok(None);
+    };
+    let named = match eval(expr, context)? {
+        Value::Null => return Ok(None),
+        Value::Array(items) if !opts.iter().any(|o| o.value.loosely_equals(&Value::Array(items.clone()))) => items,
+        value => vec![value],
+    };
+    Ok(Some(
+        opts.iter()
+            .enumerate()
+            .filter(|(_, opt)| named.iter().any(|value| opt.matches(value)))
+            .map(|(i, _)| i)
+            .collect(),
+    ))
+}
+
+fn default_index(default: &Option<Expr>, context: &Context, opts: &[Item]) -> Result<Option<usize>> {
+    Ok(default_indexes(default, context, opts)?.and_then(|picked| picked.first().copied()))
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct TextPrompt {
-    pub message: String,
+    pub message: Template,
+    pub default: Option<Expr>,
+    pub when: Option<Expr>,
+    pub validation: Option<Validation>,
 }
 
 impl Prompt for TextPrompt {
-    fn run(&self, name: &str, context: &mut Context) -> Result<()> {
-        let mut p = Readline::default()
-            .title(&self.message)
-            .prompt()
-            .map_err(|e| Error::FailedToCreatePrompt(e.to_string()))?;
+    fn run(&self, key: &str, name: &str, context: &mut Context, backend: &mut dyn PromptBackend) -> Result<Flow> {
+        let message = self.message.render(context)?;
+        let request = Request { key, name, message: &message, error: None };
+        let default = default_text(&self.default, context)?;
 
-        let result = p.run().map_err(|e| Error::FailedToRunPrompt(e.to_string()))?;
-        context.set_variable(name.to_string(), Value::String(result));
-
-        Ok(())
+        let accept = accept_text(self.validation.as_ref());
+        ask_until_accepted(request, default, context, |request, default| backend.text(request, default), accept)
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct ConfirmPrompt {
-    pub message: String,
+    pub message: Template,
+    pub default: Option<Expr>,
+    pub when: Option<Expr>,
 }
 
 impl Prompt for ConfirmPrompt {
-    fn run(&self, name: &str, context: &mut Context) -> Result<()> {
-        let mut p = Confirm::new(&self.message)
-            .prompt()
-            .map_err(|e| Error::FailedToCreatePrompt(e.to_string()))?;
-
-        let result = p.run().map_err(|e| Error::FailedToRunPrompt(e.to_string()))?;
-        context.set_variable(name.to_string(), Value::Boolean(match result.as_str() {
-            "yes" | "y" | "true" => true,
-            "no" | "n" | "false" => false,
-            _ => return Err(Error::FailedToRunPrompt(format!("Invalid confirmation response: {}", result))),
-        }));
+    fn run(&self, key: &str, name: &str, context: &mut Context, backend: &mut dyn PromptBackend) -> Result<Flow> {
+        let message = self.message.render(context)?;
+        let request = Request { key, name, message: &message, error: None };
+        let default = default_confirm(&self.default, context)?;
+        let response = backend.confirm(&request, default)?;
 
-        Ok(())
+        Ok(store(response, name, context, Value::Boolean))
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct PasswordPrompt {
-    pub message: String,
+    pub message: Template,
+    pub default: Option<Expr>,
+    pub when: Option<Expr>,
+    pub validation: Option<Validation>,
 }
 
 impl Prompt for PasswordPrompt {
-    fn run(&self, name: &str, context: &mut Context) -> Result<()> {
-        let mut p = Password::default()
-            .title(&self.message)
-            .prompt()
-            .map_err(|e| Error::FailedToCreatePrompt(e.to_string()))?;
+    fn run(&self, key: &str, name: &str, context: &mut Context, backend: &mut dyn PromptBackend) -> Result<Flow> {
+        let message = self.message.render(context)?;
+        let request = Request { key, name, message: &message, error: None };
+        let default = default_text(&self.default, context)?;
 
-        let result = p.run().map_err(|e| Error::FailedToRunPrompt(e.to_string()))?;
-        context.set_variable(name.to_string(), Value::String(result));
+        let accept = accept_text(self.validation.as_ref());
+        ask_until_accepted(request, default, context, |request, default| backend.password(request, default), accept)
+    }
+}
 
-        Ok(())
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumberPrompt {
+    pub message: Template,
+    pub default: Option<Expr>,
+    pub when: Option<Expr>,
+    pub bounds: Bounds,
+}
+
+impl Prompt for NumberPrompt {
+    fn run(&self, key: &str, name: &str, context: &mut Context, backend: &mut dyn PromptBackend) -> Result<Flow> {
+        let message = self.message.render(context)?;
+        let request = Request { key, name, message: &message, error: None };
+        let default = default_text(&self.default, context)?;
+
+        let accept = accept_number(self.bounds.eval(context)?, false);
+        ask_until_accepted(request, default, context, |request, default| backend.text(request, default), accept)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntegerPrompt {
+    pub message: Template,
+    pub default: Option<Expr>,
+    pub when: Option<Expr>,
+    pub bounds: Bounds,
+}
+
+impl Prompt for IntegerPrompt {
+    fn run(&self, key: &str, name: &str, context: &mut Context, backend: &mut dyn PromptBackend) -> Result<Flow> {
+        let message = self.message.render(context)?;
+        let request = Request { key, name, message: &message, error: None };
+        let default = default_text(&self.default, context)?;
+
+        let accept = accept_number(self.bounds.eval(context)?, true);
+        ask_until_accepted(request, default, context, |request, default| backend.text(request, default), accept)
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct SelectPrompt {
-    pub message: String,
-    pub options: Vec<Expr>,
+    pub message: Template,
+    pub options: Vec<Choice>,
+    pub default: Option<Expr>,
+    pub when: Option<Expr>,
 }
 
 impl Prompt for SelectPrompt {
-    fn run(&self, name: &str, context: &mut Context) -> Result<()> {
-        let opts = self.options.iter().map(|expr| eval(expr, context)).collect::<Result<Vec<_>>>()?;
-        let opts = get_options(opts, context);
+    fn run(&self, key: &str, name: &str, context: &mut Context, backend: &mut dyn PromptBackend) -> Result<Flow> {
+        let mut opts = eval_options(&self.options, context)?;
 
-        let mut p = Listbox::new(&opts)
-            .title(&self.message)
-            .prompt()
-            .map_err(|e| Error::FailedToCreatePrompt(e.to_string()))?;
+        let message = self.message.render(context)?;
+        let request = Request { key, name, message: &message, error: None };
+        let default = default_index(&self.default, context, &opts)?;
+        let response = backend.select(&request, &opts, default)?;
 
-        let result = p.run().map_err(|e| Error::FailedToRunPrompt(e.to_string()))?;
-        context.set_variable(name.to_string(), Value::String(result));
-
-        Ok(())
+        Ok(store(response, name, context, |i| opts.swap_remove(i).value))
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct MultiSelectPrompt {
-    pub message: String,
-    pub options: Vec<Expr>,
+    pub message: Template,
+    pub options: Vec<Choice>,
+    pub default: Option<Expr>,
+    pub when: Option<Expr>,
 }
 
 impl Prompt for MultiSelectPrompt {
-    fn run(&self, name: &str, context: &mut Context) -> Result<()> {
-        let opts = self.options.iter().map(|expr| eval(expr, context)).collect::<Result<Vec<_>>>()?;
-        let opts = get_options(opts, context);
+    fn run(&self, key: &str, name: &str, context: &mut Context, backend: &mut dyn PromptBackend) -> Result<Flow> {
+        let opts = eval_options(&self.options, context)?;
 
-        let mut p = Checkbox::new(&opts)
-            .title(&self.message)
-            .prompt()
-            .map_err(|e| Error::FailedToCreatePrompt(e.to_string()))?;
-        let result = p.run().map_err(|e| Error::FailedToRunPrompt(e.to_string()))?;
-        context.set_variable(name.to_string(), Value::Array(result.into_iter().map(|s| Value::String(s)).collect()));
+        let message = self.message.render(context)?;
+        let request = Request { key, name, message: &message, error: None };
+        let default = default_indexes(&self.default, context, &opts)?;
+        let response = backend.multi_select(&request, &opts, default.as_deref())?;
 
-        Ok(())
+        Ok(store(response, name, context, |picked| {
+            Value::Array(picked.into_iter().map(|i| opts[i].value.clone()).collect())
+        }))
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct FuzzySelectPrompt {
-    pub message: String,
-    pub options: Vec<Expr>,
+    pub message: Template,
+    pub options: Vec<Choice>,
+    pub default: Option<Expr>,
+    pub when: Option<Expr>,
 }
 
 impl Prompt for FuzzySelectPrompt {
-    fn run(&self, name: &str, context: &mut Context) -> Result<()> {
-        let options = self.options.iter().map(|expr| eval(expr, context)).collect::<Result<Vec<_>>>()?;
-        let opts = get_options(options, context);
-
-        let mut p = QuerySelector::new(&opts, |input, opts| {
-            opts.into_iter()
-                .filter(|opt| opt.to_lowercase().contains(&input.to_lowercase()))
-                .map(|x| x.clone())
-                .collect()
-        })
-        .title(&self.message)
-        .prompt()
-        .map_err(|e| Error::FailedToCreatePrompt(e.to_string()))?;
-
-        let result = p.run().map_err(|e| Error::FailedToRunPrompt(e.to_string()))?;
-        context.set_variable(name.to_string(), Value::String(result));
-
-        Ok(())
+    fn run(&self, key: &str, name: &str, context: &mut Context, backend: &mut dyn PromptBackend) -> Result<Flow> {
+        let mut opts = eval_options(&self.options, context)?;
+
+        let message = self.message.render(context)?;
+        let request = Request { key, name, message: &message, error: None };
+        let default = default_index(&self.default, context, &opts)?;
+        let response = backend.fuzzy_select(&request, &opts, default)?;
+
+        Ok(store(response, name, context, |i| opts.swap_remove(i).value))
     }
 }
@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+
+use crate::{
+    backend::{Item, PromptBackend, Request, Response},
+    error::Result,
+    eval::Context,
+    parser::Value,
+    prompt::PromptType,
+    state::{HistoryEntry, Node, State},
+    template::{to_text, Template},
+};
+
+/// Label of the option that finishes the review.
+const PROCEED: &str = "Continue";
+
+/// Lists the answers given so far and lets the user pick one to answer
+/// again before moving on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Review {
+    pub message: Template,
+}
+
+impl Review {
+    /// Returns the key of the prompt state to answer again, or `None` to
+    /// proceed.
+    pub fn run(
+        &self,
+        key: &str,
+        name: &str,
+        nodes: &HashMap<String, Node>,
+        history: &[HistoryEntry],
+        context: &Context,
+        backend: &mut dyn PromptBackend,
+    ) -> Result<Response<Option<String>>> {
+        // A prompt answered several times, e.g. in a loop, is listed once
+        // with its latest answer.
+        let mut answered: Vec<&HistoryEntry> = Vec::new();
+        for entry in history.iter().rev() {
+            if !answered.iter().any(|a| a.key == entry.key) {
+                answered.push(entry);
+            }
+        }
+        answered.reverse();
+
+        let mut items = Vec::new();
+        for entry in answered {
+            let Some(Node {
+                name,
+                state: State::Prompt(prompt, _),
+            }) = nodes.get(&entry.key)
+            else {
+                continue;
+            };
+
+            let answer = match (prompt, context.get_context().get(name)) {
+                (_, None) => String::new(),
+                (PromptType::Password(_), Some(_)) => "********".to_string(),
+                (_, Some(value)) => to_text(value),
+            };
+            items.push(Item {
+                label: prompt.render_message(&entry.context)?,
+                description: Some(answer),
+                value: Value::String(entry.key.clone()),
+            });
+        }
+        items.push(Item {
+            label: PROCEED.to_string(),
+            description: None,
+            value: Value::Null,
+        });
+
+        let message = self.message.render(context)?;
+        let request = Request {
+            key,
+            name,
+            message: &message,
+            error: None,
+        };
+        Ok(match backend.select(&request, &items, Some(items.len() - 1))? {
+            Response::Submit(i) => Response::Submit(match &items[i].value {
+                Value::String(key) => Some(key.clone()),
+                _ => None,
+            }),
+            Response::Back => Response::Back,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::toml::load;
+
+    /// Records the options of the review and picks one of them.
+    struct Recorder {
+        items: Vec<Item>,
+        pick: Option<usize>,
+    }
+
+    impl PromptBackend for Recorder {
+        fn text(&mut self, _: &Request, _: Option<&str>) -> Result<Response<String>> {
+            unreachable!()
+        }
+
+        fn password(&mut self, _: &Request, _: Option<&str>) -> Result<Response<String>> {
+            unreachable!()
+        }
+
+        fn confirm(&mut self, _: &Request, _: Option<bool>) -> Result<Response<bool>> {
+            unreachable!()
+        }
+
+        fn select(&mut self, _: &Request, options: &[Item], default: Option<usize>) -> Result<Response<usize>> {
+            self.items = options.to_vec();
+            Ok(Response::Submit(self.pick.or(default).unwrap()))
+        }
+
+        fn multi_select(&mut self, _: &Request, _: &[Item], _: Option<&[usize]>) -> Result<Response<Vec<usize>>> {
+            unreachable!()
+        }
+
+        fn fuzzy_select(&mut self, _: &Request, _: &[Item], _: Option<usize>) -> Result<Response<usize>> {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn test_review() {
+        let content = r#"
+            start = "tag"
+
+            [state.tag]
+            type = "text"
+            name = "tag"
+            message = "Tag ${len($tags)}"
+            to = "secret"
+
+            [state.secret]
+            type = "password"
+            name = "secret"
+            message = "Secret"
+            to = "review"
+
+            [state.review]
+            type = "review"
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+        let (_, nodes) = load(content).unwrap();
+
+        let mut context = Context::new(HashMap::new());
+        let mut history = Vec::new();
+        for tags in [0, 1] {
+            context.set_variable("tags".to_string(), Value::Array(vec![Value::Null; tags]));
+            history.push(HistoryEntry {
+                key: "tag".to_string(),
+                context: context.clone(),
+                answer: None,
+            });
+        }
+        history.push(HistoryEntry {
+            key: "secret".to_string(),
+            context: context.clone(),
+            answer: None,
+        });
+        context.set_variable("tag".to_string(), Value::String("b".to_string()));
+        context.set_variable("secret".to_string(), Value::String("hunter2".to_string()));
+
+        let review = Review {
+            message: Template::parse("Check").unwrap(),
+        };
+        let mut backend = Recorder {
+            items: Vec::new(),
+            pick: None,
+        };
+        let picked = review.run("review", "review", &nodes, &history, &context, &mut backend);
+        assert_eq!(picked, Ok(Response::Submit(None)));
+        // A prompt answered twice is listed once, with the message it was
+        // last asked with.
+        assert_eq!(
+            backend.items,
+            vec![
+                Item {
+                    label: "Tag 1".to_string(),
+                    description: Some("b".to_string()),
+                    value: Value::String("tag".to_string()),
+                },
+                Item {
+                    label: "Secret".to_string(),
+                    description: Some("********".to_string()),
+                    value: Value::String("secret".to_string()),
+                },
+                Item {
+                    label: PROCEED.to_string(),
+                    description: None,
+                    value: Value::Null,
+                },
+            ]
+        );
+
+        backend.pick = Some(1);
+        let picked = review.run("review", "review", &nodes, &history, &context, &mut backend);
+        assert_eq!(picked, Ok(Response::Submit(Some("secret".to_string()))));
+    }
+}
@@ -0,0 +1,44 @@
+use crate::{eval::Context, parser::Value};
+
+/// Bookkeeping shared by the states that run `body` in rounds, `collect`
+/// and `foreach`. The index of the running round is kept in the context
+/// under the key of the looping state, so that going back or resuming a
+/// session restores it together with the variables.
+pub struct Rounds<'a> {
+    pub key: &'a str,
+    pub body: &'a str,
+    pub to: &'a str,
+}
+
+impl Rounds<'_> {
+    /// Returns the index of the round that just finished, or `None` when the
+    /// loop is entered for the first time, in which case `name` is set to
+    /// `empty` to gather the rounds into.
+    pub fn finished(&self, context: &mut Context, name: &str, empty: Value) -> Option<usize> {
+        let finished = context.loop_iteration(self.key);
+        if finished.is_none() {
+            context.set_variable(name.to_string(), empty);
+        }
+        finished
+    }
+
+    /// Starts round `index` and returns the state it begins at.
+    pub fn next(&self, context: &mut Context, index: usize) -> String {
+        context.set_loop_iteration(self.key, index);
+        self.body.to_string()
+    }
+
+    /// Ends the loop and returns the state after it.
+    pub fn end(&self, context: &mut Context) -> String {
+        context.end_loop(self.key);
+        self.to.to_string()
+    }
+}
+
+/// Takes a variable a round left behind out of the context, so that the
+/// next round does not see it.
+pub fn take(context: &mut Context, name: &str) -> Value {
+    let value = context.get_context().get(name).cloned().unwrap_or(Value::Null);
+    context.remove_variable(name.to_string());
+    value
+}
@@ -0,0 +1,444 @@
+use std::{collections::HashMap, fmt};
+
+use serde::Deserialize;
+use toml::Table;
+
+use crate::{
+    backend::ScriptedBackend,
+    error::{Error, Result},
+    eval::Context,
+    parser::Value,
+    prompt::PromptType,
+    state::{Node, State, StateMachine},
+    toml::to_value,
+};
+
+/// A file of wizard regression scenarios.
+///
+/// ```toml
+/// [[scenario]]
+/// name = "adult"
+/// args = { country = "JP" }
+/// path = ["name", "age", "age_condition", "gender", "end"]
+///
+/// [scenario.inputs]
+/// name = "John"
+/// age = "20"
+/// gender = "Male"
+///
+/// [scenario.expect]
+/// name = "John"
+/// age = "20"
+/// gender = "Male"
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct ScenarioFile {
+    #[serde(rename = "scenario", default)]
+    pub scenarios: Vec<Scenario>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Scenario {
+    pub name: String,
+    #[serde(default)]
+    pub args: Table,
+    /// Answers keyed by state key or variable name. An array answers a
+    /// state once per element, in order, for states visited repeatedly;
+    /// `multi_select` states use an array of arrays for that.
+    #[serde(default)]
+    pub inputs: Table,
+    /// The expected final context, excluding `args`.
+    pub expect: Option<Table>,
+    /// The expected keys of the visited states, in order.
+    pub path: Option<Vec<String>>,
+    /// The expected keys of the prompts skipped by their `when`, in order.
+    pub skipped: Option<Vec<String>>,
+}
+
+pub fn parse_scenarios(content: &str) -> Result<Vec<Scenario>> {
+    let file: ScenarioFile =
+        toml::from_str(content).map_err(|e| Error::ParseError(e.to_string()))?;
+    Ok(file.scenarios)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Difference {
+    Missing(String, Value),
+    Unexpected(String, Value),
+    Changed {
+        name: String,
+        expected: Value,
+        actual: Value,
+    },
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difference::Missing(name, expected) => write!(f, "- {}: {}", name, show(expected)),
+            Difference::Unexpected(name, actual) => write!(f, "+ {}: {}", name, show(actual)),
+            Difference::Changed {
+                name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "- {}: {}\n+ {}: {}",
+                name,
+                show(expected),
+                name,
+                show(actual)
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Failure {
+    /// The wizard stopped with an error.
+    Error(Error),
+    Context(Vec<Difference>),
+    Path {
+        expected: Vec<String>,
+        actual: Vec<String>,
+    },
+    Skipped {
+        expected: Vec<String>,
+        actual: Vec<String>,
+    },
+    /// Inputs that the wizard never asked for.
+    UnusedInputs(usize),
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Error(e) => write!(f, "error: {}", e),
+            Failure::Context(differences) => {
+                write!(f, "context differs (- expected, + actual):")?;
+                for difference in differences {
+                    for line in difference.to_string().lines() {
+                        write!(f, "\n    {}", line)?;
+                    }
+                }
+                Ok(())
+            }
+            Failure::Path { expected, actual } => write!(
+                f,
+                "path differs:\n    expected: {}\n    actual:   {}",
+                expected.join(" -> "),
+                actual.join(" -> ")
+            ),
+            Failure::Skipped { expected, actual } => write!(
+                f,
+                "skipped prompts differ:\n    expected: [{}]\n    actual:   [{}]",
+                expected.join(", "),
+                actual.join(", ")
+            ),
+            Failure::UnusedInputs(n) => write!(f, "{} input(s) were never used", n),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub name: String,
+    pub failures: Vec<Failure>,
+}
+
+impl Outcome {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Runs a scenario headlessly against a loaded wizard.
+pub fn run_scenario(start: &str, nodes: &HashMap<String, Node>, scenario: &Scenario) -> Outcome {
+    let mut failures = Vec::new();
+
+    let mut backend = script(nodes, &scenario.inputs);
+    let args = scenario
+        .args
+        .clone()
+        .into_iter()
+        .map(|(k, v)| (k, to_value(v)))
+        .collect();
+
+    let mut sm = StateMachine::new(nodes.clone(), Context::new(args)).with_backend(&mut backend);
+    if let Err(e) = sm.run(start.to_string()) {
+        failures.push(Failure::Error(e));
+    }
+
+    if let Some(expect) = &scenario.expect {
+        let mut actual = sm.context.get_context().clone();
+        actual.remove("args");
+        let expected = expect
+            .clone()
+            .into_iter()
+            .map(|(k, v)| (k, to_value(v)))
+            .collect();
+
+        let differences = diff(&expected, &actual);
+        if !differences.is_empty() {
+            failures.push(Failure::Context(differences));
+        }
+    }
+
+    if let Some(path) = &scenario.path {
+        if *path != sm.path {
+            failures.push(Failure::Path {
+                expected: path.clone(),
+                actual: sm.path.clone(),
+            });
+        }
+    }
+
+    if let Some(skipped) = &scenario.skipped {
+        if *skipped != sm.skipped {
+            failures.push(Failure::Skipped {
+                expected: skipped.clone(),
+                actual: sm.skipped.clone(),
+            });
+        }
+    }
+
+    drop(sm);
+    let unused = backend.remaining();
+    if unused > 0 && failures.is_empty() {
+        failures.push(Failure::UnusedInputs(unused));
+    }
+
+    Outcome {
+        name: scenario.name.clone(),
+        failures,
+    }
+}
+
+fn script(nodes: &HashMap<String, Node>, inputs: &Table) -> ScriptedBackend {
+    let mut backend = ScriptedBackend::new();
+    for (key, input) in inputs {
+        let multi_select = nodes.iter().any(|(k, node)| {
+            matches!(&node.state, State::Prompt(PromptType::MultiSelect(_), _))
+                && (k == key || node.name == *key)
+        });
+
+        let value = to_value(input.clone());
+        let repeated = match &value {
+            Value::Array(items) if multi_select => {
+                !items.is_empty() && items.iter().all(|i| matches!(i, Value::Array(_)))
+            }
+            Value::Array(_) => true,
+            _ => false,
+        };
+
+        match value {
+            Value::Array(items) if repeated => {
+                for item in items {
+                    backend = backend.answer(key.clone(), item);
+                }
+            }
+            value => backend = backend.answer(key.clone(), value),
+        }
+    }
+    backend
+}
+
+fn diff(expected: &HashMap<String, Value>, actual: &HashMap<String, Value>) -> Vec<Difference> {
+    let mut names: Vec<&String> = expected.keys().chain(actual.keys()).collect();
+    names.sort();
+    names.dedup();
+
+    names
+        .into_iter()
+        .filter_map(|name| match (expected.get(name), actual.get(name)) {
+            (Some(e), Some(a)) if e == a => None,
+            (Some(e), Some(a)) => Some(Difference::Changed {
+                name: name.clone(),
+                expected: e.clone(),
+                actual: a.clone(),
+            }),
+            (Some(e), None) => Some(Difference::Missing(name.clone(), e.clone())),
+            (None, Some(a)) => Some(Difference::Unexpected(name.clone(), a.clone())),
+            (None, None) => None,
+        })
+        .collect()
+}
+
+fn show(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("{:?}", s),
+        Value::Number(n) => n.to_string(),
+        Value::Boolean(b) => b.to_string(),
+        Value::Symbol(s) => s.clone(),
+        Value::Null => "null".to_string(),
+        Value::Array(items) => format!(
+            "[{}]",
+            items.iter().map(show).collect::<Vec<_>>().join(", ")
+        ),
+        Value::Object(o) => {
+            let mut entries: Vec<_> = o.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            format!(
+                "{{{}}}",
+                entries
+                    .into_iter()
+                    .map(|(k, v)| format!("{:?}: {}", k, show(v)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::toml::load;
+
+    const WIZARD: &str = r#"
+        start = "name"
+
+        [state.name]
+        type = "text"
+        name = "name"
+        message = "What is your name?"
+        to = "age"
+
+        [state.age]
+        type = "text"
+        name = "age"
+        message = "What is your age?"
+        to = "age_condition"
+
+        [state.age_condition]
+        type = "condition"
+        condition = "$age > $args.adult"
+        branches = { true = "features", false = "end" }
+
+        [state.features]
+        type = "multi_select"
+        name = "features"
+        message = "Features?"
+        options = ["logging", "metrics"]
+        to = "end"
+
+        [state.end]
+        type = "done"
+    "#;
+
+    fn run(scenarios: &str) -> Vec<Outcome> {
+        let (start, nodes) = load(WIZARD).unwrap();
+        parse_scenarios(scenarios)
+            .unwrap()
+            .iter()
+            .map(|s| run_scenario(&start, &nodes, s))
+            .collect()
+    }
+
+    #[test]
+    fn test_scenario_pass() {
+        let outcomes = run(r#"
+            [[scenario]]
+            name = "adult"
+            args = { adult = 18 }
+            path = ["name", "age", "age_condition", "features", "end"]
+            inputs = { name = "John", age = "20", features = ["metrics"] }
+            expect = { name = "John", age = "20", features = ["metrics"] }
+
+            [[scenario]]
+            name = "child"
+            args = { adult = 18 }
+            inputs = { name = "Ken", age = "10" }
+            path = ["name", "age", "age_condition", "end"]
+        "#);
+
+        assert_eq!(outcomes.len(), 2);
+        assert!(outcomes.iter().all(|o| o.passed()), "{:?}", outcomes);
+    }
+
+    #[test]
+    fn test_scenario_failures() {
+        let outcomes = run(r#"
+            [[scenario]]
+            name = "wrong"
+            args = { adult = 18 }
+            path = ["name", "age", "end"]
+            inputs = { name = "John", age = "20", features = [] }
+            expect = { name = "Jane", age = "20", extra = true }
+        "#);
+
+        assert_eq!(
+            outcomes[0].failures,
+            vec![
+                Failure::Context(vec![
+                    Difference::Missing("extra".to_string(), Value::Boolean(true)),
+                    Difference::Unexpected("features".to_string(), Value::Array(vec![])),
+                    Difference::Changed {
+                        name: "name".to_string(),
+                        expected: Value::String("Jane".to_string()),
+                        actual: Value::String("John".to_string()),
+                    },
+                ]),
+                Failure::Path {
+                    expected: vec!["name".to_string(), "age".to_string(), "end".to_string()],
+                    actual: vec![
+                        "name".to_string(),
+                        "age".to_string(),
+                        "age_condition".to_string(),
+                        "features".to_string(),
+                        "end".to_string(),
+                    ],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scenario_compares_types() {
+        let outcomes = run(r#"
+            [[scenario]]
+            name = "number"
+            args = { adult = 18 }
+            inputs = { name = "Ken", age = "10" }
+            expect = { name = "Ken", age = 10 }
+        "#);
+
+        assert_eq!(
+            outcomes[0].failures,
+            vec![Failure::Context(vec![Difference::Changed {
+                name: "age".to_string(),
+                expected: Value::Number(10.0),
+                actual: Value::String("10".to_string()),
+            }])]
+        );
+    }
+
+    #[test]
+    fn test_scenario_missing_input() {
+        let outcomes = run(r#"
+            [[scenario]]
+            name = "incomplete"
+            args = { adult = 18 }
+            inputs = { name = "John" }
+        "#);
+
+        assert_eq!(
+            outcomes[0].failures,
+            vec![Failure::Error(Error::MissingAnswer(
+                "state 'age' (variable 'age')".to_string()
+            ))]
+        );
+    }
+
+    #[test]
+    fn test_scenario_repeated_inputs() {
+        let outcomes = run(r#"
+            [[scenario]]
+            name = "unused"
+            args = { adult = 18 }
+            inputs = { name = ["John", "Jane"], age = "10" }
+        "#);
+
+        assert_eq!(outcomes[0].failures, vec![Failure::UnusedInputs(1)]);
+    }
+}
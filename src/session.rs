@@ -0,0 +1,137 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    eval::Context,
+    state::HistoryEntry,
+};
+
+/// The progress of an interrupted run: the state to enter next and
+/// everything the state machine had collected before it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Hash of the wizard file the run was started with.
+    pub wizard: String,
+    pub current: String,
+    pub context: Context,
+    pub history: Vec<HistoryEntry>,
+    pub path: Vec<String>,
+    #[serde(default)]
+    pub skipped: Vec<String>,
+}
+
+/// A file that a run is checkpointed to after every state transition.
+#[derive(Debug, Clone)]
+pub struct Session {
+    path: PathBuf,
+    wizard: String,
+}
+
+impl Session {
+    /// `wizard` is the content of the wizard file being run.
+    pub fn new(path: impl Into<PathBuf>, wizard: &str) -> Self {
+        Self {
+            path: path.into(),
+            wizard: content_hash(wizard),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Hash of the wizard file, recorded in every checkpoint.
+    pub fn wizard(&self) -> &str {
+        &self.wizard
+    }
+
+    pub fn save(&self, checkpoint: &Checkpoint) -> Result<()> {
+        let content = serde_json::to_string(checkpoint).map_err(|e| self.error(e))?;
+
+        // Write to a temporary file first so that an interruption while
+        // saving never leaves a truncated checkpoint behind.
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, content).map_err(|e| self.error(e))?;
+        std::fs::rename(&tmp, &self.path).map_err(|e| self.error(e))
+    }
+
+    /// Loads the checkpoint, refusing it when the wizard file has changed
+    /// since it was saved.
+    pub fn load(&self) -> Result<Checkpoint> {
+        let content = std::fs::read_to_string(&self.path).map_err(|e| self.error(e))?;
+        let checkpoint: Checkpoint = serde_json::from_str(&content).map_err(|e| self.error(e))?;
+
+        if checkpoint.wizard != self.wizard {
+            return Err(self.error("the wizard file has changed since the session was saved"));
+        }
+        Ok(checkpoint)
+    }
+
+    /// Deletes the session file once the run has finished.
+    pub fn clear(&self) -> Result<()> {
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(self.error(e)),
+            _ => Ok(()),
+        }
+    }
+
+    fn error(&self, reason: impl ToString) -> Error {
+        Error::SessionError(format!("{}: {}", self.path.display(), reason.to_string()))
+    }
+}
+
+/// 64-bit FNV-1a, which unlike `DefaultHasher` is stable across builds.
+fn content_hash(content: &str) -> String {
+    let hash = content.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::parser::Value;
+
+    fn checkpoint(session: &Session) -> Checkpoint {
+        let mut context = Context::new(HashMap::new());
+        context.set_variable("name".to_string(), Value::Symbol("John".to_string()));
+        Checkpoint {
+            wizard: session.wizard().to_string(),
+            current: "age".to_string(),
+            context: context.clone(),
+            history: vec![HistoryEntry {
+                key: "name".to_string(),
+                context,
+                answer: Some(Value::Symbol("John".to_string())),
+            }],
+            path: vec!["name".to_string()],
+            skipped: vec![],
+        }
+    }
+
+    #[test]
+    fn test_session_round_trip() {
+        let path = std::env::temp_dir().join(format!("promptoml-session-{}.json", std::process::id()));
+        let session = Session::new(&path, "start = \"name\"");
+        session.save(&checkpoint(&session)).unwrap();
+
+        let loaded = session.load().unwrap();
+        assert_eq!(loaded.current, "age");
+        assert_eq!(
+            loaded.context.get_context().get("name"),
+            Some(&Value::Symbol("John".to_string()))
+        );
+        assert_eq!(loaded.history.len(), 1);
+
+        let changed = Session::new(&path, "start = \"age\"");
+        assert!(matches!(changed.load(), Err(Error::SessionError(_))));
+
+        session.clear().unwrap();
+        assert!(!path.exists());
+    }
+}
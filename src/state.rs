@@ -1,12 +1,26 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    backend::{PromkitBackend, PromptBackend, Replay, Response},
+    call::Call,
+    collect::Collect,
     condition::Condition,
     error::{Error, Result},
     eval::{eval, Context},
-    parser::Expr,
-    prompt::{Prompt, PromptType},
+    exec::Exec,
+    foreach::Foreach,
+    parser::{Expr, Value},
+    prompt::{Flow, Prompt, PromptType},
     goto::Goto,
+    review::Review,
+    session::{Checkpoint, Session},
+    switch::Switch,
+    toml::canonical,
 };
 
 #[derive(Debug, Clone, PartialEq)]
@@ -14,120 +28,1633 @@ pub enum State {
     Prompt(PromptType, String),
     Condition(Condition),
     Goto(Goto),
+    Switch(Switch),
     Set(Expr, String),
     Remove(String),
+    Call(Call, String),
+    Exec(Exec),
+    Review(Review, String),
+    Collect(Collect),
+    Foreach(Foreach),
     Done,
 }
 
+/// A transition out of a state: either a state key, or an expression that
+/// `condition` and `goto` evaluate to one.
+#[derive(Debug, PartialEq)]
+pub enum Target<K, E> {
+    Key(K),
+    Expr(E),
+}
+
+impl State {
+    /// Every transition out of the state, labelled with the branch that
+    /// takes it when the state has several.
+    pub fn targets(&self) -> Vec<(Option<String>, Target<&String, &Expr>)> {
+        match self {
+            State::Prompt(_, to)
+            | State::Set(_, to)
+            | State::Remove(to)
+            | State::Call(_, to)
+            | State::Review(_, to) => vec![(None, Target::Key(to))],
+            State::Condition(condition) => {
+                let mut branches: Vec<_> = condition.branches.iter().collect();
+                branches.sort_by(|a, b| a.0.cmp(b.0));
+                branches
+                    .into_iter()
+                    .map(|(branch, target)| (Some(branch.clone()), Target::Expr(target)))
+                    .collect()
+            }
+            State::Goto(goto) => vec![(None, Target::Expr(&goto.target))],
+            State::Switch(switch) => switch
+                .cases
+                .iter()
+                .enumerate()
+                .map(|(i, (_, to))| (format!("case {}", i + 1), to))
+                .chain(std::iter::once(("default".to_string(), &switch.default)))
+                .map(|(label, to)| (Some(label), Target::Key(to)))
+                .collect(),
+            State::Exec(exec) => std::iter::once(("success", &exec.on_success))
+                .chain(exec.on_failure.iter().map(|to| ("failure", to)))
+                .map(|(label, to)| (Some(label.to_string()), Target::Key(to)))
+                .collect(),
+            State::Collect(Collect { body, to, .. }) | State::Foreach(Foreach { body, to, .. }) => vec![
+                (Some("each".to_string()), Target::Key(body)),
+                (Some("done".to_string()), Target::Key(to)),
+            ],
+            State::Done => vec![],
+        }
+    }
+
+    /// The transitions of [`State::targets`], for rewriting them in place.
+    pub fn targets_mut(&mut self) -> Vec<Target<&mut String, &mut Expr>> {
+        match self {
+            State::Prompt(_, to)
+            | State::Set(_, to)
+            | State::Remove(to)
+            | State::Call(_, to)
+            | State::Review(_, to) => vec![Target::Key(to)],
+            State::Condition(condition) => condition.branches.values_mut().map(Target::Expr).collect(),
+            State::Goto(goto) => vec![Target::Expr(&mut goto.target)],
+            State::Switch(switch) => switch
+                .cases
+                .iter_mut()
+                .map(|(_, to)| to)
+                .chain(std::iter::once(&mut switch.default))
+                .map(Target::Key)
+                .collect(),
+            State::Exec(exec) => std::iter::once(&mut exec.on_success)
+                .chain(&mut exec.on_failure)
+                .map(Target::Key)
+                .collect(),
+            State::Collect(Collect { body, to, .. }) | State::Foreach(Foreach { body, to, .. }) => {
+                vec![Target::Key(body), Target::Key(to)]
+            }
+            State::Done => vec![],
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Node {
     pub name: String,
     pub state: State,
 }
 
-pub struct StateMachine {
+/// A prompt that has been answered, together with the context as it was
+/// right before the prompt ran.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub key: String,
+    pub context: Context,
+    /// The value the prompt stored, given again when the path after a
+    /// review is run again.
+    #[serde(default)]
+    pub answer: Option<Value>,
+}
+
+pub struct StateMachine<'a> {
     pub context: Context,
     pub nodes: HashMap<String, Node>,
+    pub history: Vec<HistoryEntry>,
+    /// Keys of every state entered, in order.
+    pub path: Vec<String>,
+    /// Keys of the prompts skipped because their `when` did not hold, in
+    /// order.
+    pub skipped: Vec<String>,
+    backend: Box<dyn PromptBackend + 'a>,
+    session: Option<Session>,
+    /// Canonical paths of the wizard files being run, outermost first, so
+    /// that a wizard calling itself is caught.
+    wizards: Vec<PathBuf>,
+    /// Set for a called wizard, which hands going back from its first
+    /// prompt to the caller.
+    nested: bool,
 }
 
-impl StateMachine {
+impl<'a> StateMachine<'a> {
     pub fn new(nodes: HashMap<String, Node>, context: Context) -> Self {
-        Self { nodes, context }
+        Self {
+            nodes,
+            context,
+            history: Vec::new(),
+            path: Vec::new(),
+            skipped: Vec::new(),
+            backend: Box::new(PromkitBackend::default()),
+            session: None,
+            wizards: Vec::new(),
+            nested: false,
+        }
+    }
+
+    /// Replaces the interactive promkit prompts with another answer source.
+    pub fn with_backend<B: PromptBackend + 'a>(mut self, backend: B) -> Self {
+        self.backend = Box::new(backend);
+        self
+    }
+
+    /// The file the wizard was loaded from.
+    pub fn with_wizard(mut self, path: &Path) -> Self {
+        self.wizards = vec![canonical(path)];
+        self
+    }
+
+    /// Checkpoints the run to `session` after every state transition and
+    /// deletes the checkpoint once the run is done.
+    pub fn with_session(mut self, session: Session) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    /// Restores an interrupted run and continues it from the state it was
+    /// about to enter.
+    pub fn resume(&mut self, checkpoint: Checkpoint) -> Result<()> {
+        // Only the main wizard file is hashed into the checkpoint, so an
+        // edited include or called wizard can drop states it refers to.
+        let keys = std::iter::once(&checkpoint.current)
+            .chain(checkpoint.history.iter().map(|h| &h.key))
+            .chain(&checkpoint.path)
+            .chain(&checkpoint.skipped);
+        for key in keys {
+            if !self.nodes.contains_key(key) {
+                return Err(Error::SessionError(format!(
+                    "state '{}' of the session no longer exists in the wizard",
+                    key
+                )));
+            }
+        }
+
+        self.context = checkpoint.context;
+        self.history = checkpoint.history;
+        self.path = checkpoint.path;
+        self.skipped = checkpoint.skipped;
+        self.run(checkpoint.current)
+    }
+
+    fn checkpoint(&self, current: &str) -> Result<()> {
+        let Some(session) = &self.session else {
+            return Ok(());
+        };
+        session.save(&Checkpoint {
+            wizard: session.wizard().to_string(),
+            current: current.to_string(),
+            context: self.context.clone(),
+            history: self.history.clone(),
+            path: self.path.clone(),
+            skipped: self.skipped.clone(),
+        })
     }
 
     pub fn run(&mut self, start: String) -> Result<()> {
-        let mut current_node = self
-            .nodes
-            .get(&start)
-            .ok_or(Error::InvalidTransition(format!(
+        self.run_until(start).map(|_| ())
+    }
+
+    /// Runs until a done state, or until a called wizard goes back from its
+    /// first prompt, which returns `Flow::Back`.
+    fn run_until(&mut self, start: String) -> Result<Flow> {
+        if !self.nodes.contains_key(&start) {
+            return Err(Error::InvalidTransition(format!(
                 "Invalid start node: {}",
                 start
-            )))?;
-        while current_node.state != State::Done {
-            match &current_node.state {
-                State::Prompt(prompt, to) => {
-                    prompt.run(&current_node.name, &mut self.context)?;
-                    current_node = self.nodes.get(to).ok_or(Error::InvalidTransition(format!(
-                        "Invalid transition from {} to {}",
-                        current_node.name, to
-                    )))?;
-                }
-                State::Condition(condition) => {
-                    let result = condition.eval(&self.context)?;
-                    current_node =
-                        self.nodes
-                            .get(&result)
-                            .ok_or(Error::InvalidTransition(format!(
-                                "Invalid transition from {} to {}",
-                                current_node.name, result
-                            )))?;
-                }
-                State::Goto(goto) => {
-                    let target = goto.eval(&self.context)?;
+            )));
+        }
+
+        // Answers of the prompts after one picked in a review, given again
+        // when the path is run again and reaches them. Each is given once,
+        // so going back to the prompt asks it, and an answer the prompt now
+        // rejects is asked again.
+        let mut replay: HashMap<String, Value> = HashMap::new();
 
-                    current_node = self.nodes.get(&target).ok_or(Error::InvalidTransition(format!(
-                        "Invalid transition from {} to {}",
-                        current_node.name, target
-                    )))?;
+        let mut current = start;
+        loop {
+            self.path.push(current.clone());
+            let current_node = &self.nodes[&current];
+            let next = match &current_node.state {
+                State::Prompt(prompt, to) if !prompt.applies(&self.context)? => {
+                    match prompt.skipped_value(&self.context)? {
+                        Some(value) => self.context.set_variable(current_node.name.clone(), value),
+                        None => self.context.remove_variable(current_node.name.clone()),
+                    }
+                    self.skipped.push(current.clone());
+                    to.clone()
+                }
+                State::Prompt(prompt, to) => {
+                    let snapshot = self.context.clone();
+                    let flow = match replay.remove(&current) {
+                        Some(answer) => prompt.run(
+                            &current,
+                            &current_node.name,
+                            &mut self.context,
+                            &mut Replay::new(answer, self.backend.as_mut()),
+                        )?,
+                        None => prompt.run(
+                            &current,
+                            &current_node.name,
+                            &mut self.context,
+                            self.backend.as_mut(),
+                        )?,
+                    };
+                    match flow {
+                        Flow::Continue => {
+                            self.history.push(HistoryEntry {
+                                key: current.clone(),
+                                context: snapshot,
+                                answer: self.context.get_context().get(&current_node.name).cloned(),
+                            });
+                            to.clone()
+                        }
+                        Flow::Back => match back(&mut self.history, &mut self.context, &current, self.nested) {
+                            Some(key) => key,
+                            None => return Ok(Flow::Back),
+                        },
+                    }
                 }
+                State::Condition(condition) => condition.eval(&self.context)?,
+                State::Goto(goto) => goto.eval(&self.context)?,
+                State::Switch(switch) => switch.eval(&self.context)?,
                 State::Set(expr, to) => {
                     let value = eval(expr, &self.context)?;
                     self.context.set_variable(current_node.name.clone(), value);
-                    current_node = self.nodes.get(to).ok_or(Error::InvalidTransition(format!(
-                        "Invalid transition from {} to {}",
-                        current_node.name, to
-                    )))?;
+                    to.clone()
                 }
                 State::Remove(to) => {
                     self.context.remove_variable(current_node.name.clone());
-                    current_node = self.nodes.get(to).ok_or(Error::InvalidTransition(format!(
-                        "Invalid transition from {} to {}",
-                        current_node.name, to
-                    )))?;
+                    to.clone()
+                }
+                State::Call(call, to) => {
+                    let wizards = call.enter(&self.wizards)?;
+                    let (start, nodes) = call.load()?;
+                    let args = call.args(&self.context)?;
+                    let mut child = StateMachine::new(nodes, Context::new(args))
+                        .with_backend(self.backend.as_mut());
+                    child.wizards = wizards;
+                    child.nested = true;
+
+                    match child.run_until(start)? {
+                        Flow::Continue => {
+                            let value = call.returns(&child.context)?;
+                            self.context.set_variable(current_node.name.clone(), value);
+                            to.clone()
+                        }
+                        Flow::Back => match back(&mut self.history, &mut self.context, &current, self.nested) {
+                            Some(key) => key,
+                            None => return Ok(Flow::Back),
+                        },
+                    }
+                }
+                State::Exec(exec) => {
+                    let (value, next) = exec.run(&self.context)?;
+                    self.context.set_variable(current_node.name.clone(), value);
+                    next
+                }
+                State::Review(review, to) => {
+                    replay.clear();
+                    match review.run(
+                        &current,
+                        &current_node.name,
+                        &self.nodes,
+                        &self.history,
+                        &self.context,
+                        self.backend.as_mut(),
+                    )? {
+                        Response::Submit(None) => to.clone(),
+                        // The picked prompt is asked again from the context it
+                        // was first asked in, and the states after it run again,
+                        // so conditions see the new answer and answers from a
+                        // branch no longer taken are dropped.
+                        Response::Submit(Some(key)) => {
+                            let Some(i) = self.history.iter().rposition(|h| h.key == key) else {
+                                return Err(Error::InvalidTransition(format!(
+                                    "Review picked {}, which has not been answered",
+                                    key
+                                )));
+                            };
+                            let mut replayed = self.history.drain(i..);
+                            if let Some(picked) = replayed.next() {
+                                self.context = picked.context;
+                            }
+                            replay = replayed
+                                .filter_map(|h| Some((h.key, h.answer?)))
+                                .collect();
+                            key
+                        }
+                        Response::Back => match back(&mut self.history, &mut self.context, &current, self.nested) {
+                            Some(key) => key,
+                            None => return Ok(Flow::Back),
+                        },
+                    }
+                }
+                State::Collect(collect) => {
+                    let item = match &collect.item {
+                        Some(item) => item.clone(),
+                        None => self
+                            .nodes
+                            .get(&collect.body)
+                            .map_or_else(|| collect.body.clone(), |body| body.name.clone()),
+                    };
+                    match collect.run(
+                        &current,
+                        &current_node.name,
+                        &item,
+                        &mut self.context,
+                        self.backend.as_mut(),
+                    )? {
+                        Response::Submit(next) => next,
+                        Response::Back => match back(&mut self.history, &mut self.context, &current, self.nested) {
+                            Some(key) => key,
+                            None => return Ok(Flow::Back),
+                        },
+                    }
+                }
+                State::Foreach(foreach) => {
+                    let collect = match &foreach.collect {
+                        Some(collect) => collect.clone(),
+                        None => vec![self
+                            .nodes
+                            .get(&foreach.body)
+                            .map_or_else(|| foreach.body.clone(), |body| body.name.clone())],
+                    };
+                    foreach.run(&current, &current_node.name, &collect, &mut self.context)?
                 }
                 State::Done => {
                     break;
                 }
+            };
+
+            if !self.nodes.contains_key(&next) {
+                return Err(Error::InvalidTransition(format!(
+                    "Invalid transition from {} to {}",
+                    current_node.name, next
+                )));
             }
+            current = next;
+            self.checkpoint(&current)?;
         }
 
-        Ok(())
+        if let Some(session) = &self.session {
+            session.clear()?;
+        }
+        Ok(Flow::Continue)
+    }
+}
+
+/// Returns to the previous prompt, restoring the context from before it
+/// was answered. Non-interactive states between the two prompts are
+/// replayed once the previous prompt is answered again.
+///
+/// With no previous prompt, the current state is asked again, or `None` is
+/// returned for a called wizard to go back in its caller.
+fn back(history: &mut Vec<HistoryEntry>, context: &mut Context, current: &str, nested: bool) -> Option<String> {
+    match history.pop() {
+        Some(entry) => {
+            *context = entry.context;
+            Some(entry.key)
+        }
+        None if nested => None,
+        None => Some(current.to_string()),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::prompt::TextPrompt;
+    use crate::{
+        backend::ScriptedBackend,
+        parser::Value,
+        toml::{load, load_from},
+    };
 
     use super::*;
 
     #[test]
     fn test_state_machine() {
-        let nodes = vec![
-            (
-                "start".to_string(),
-                Node {
-                    name: "start".to_string(),
-                    state: State::Prompt(
-                        PromptType::Text(TextPrompt {
-                            message: "What is your name?".to_string(),
-                        }),
-                        "end".to_string(),
-                    ),
-                },
-            ),
-            (
-                "end".to_string(),
-                Node {
-                    name: "end".to_string(),
-                    state: State::Done,
-                },
-            ),
-        ]
-        .into_iter()
-        .collect();
-
-        let mut sm = StateMachine::new(nodes, Context::new(HashMap::new()));
-        sm.run("start".to_string()).unwrap();
+        let content = r#"
+            start = "start"
+
+            [state.start]
+            type = "text"
+            name = "name"
+            message = "What is your name?"
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+        let (start, nodes) = load(content).unwrap();
+
+        let backend = ScriptedBackend::new().answer("start", Value::String("John".to_string()));
+        let mut sm = StateMachine::new(nodes, Context::new(HashMap::new())).with_backend(backend);
+        sm.run(start).unwrap();
+
+        assert_eq!(
+            sm.context.get_context().get("name"),
+            Some(&Value::String("John".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_state_machine_back() {
+        let content = r#"
+            start = "name"
+
+            [state.name]
+            type = "text"
+            name = "name"
+            message = "What is your name?"
+            to = "greet"
+
+            [state.greet]
+            type = "set"
+            name = "greeted"
+            value = "true"
+            to = "ready"
+
+            [state.ready]
+            type = "confirm"
+            name = "ready"
+            message = "Ready?"
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+        let (start, nodes) = load(content).unwrap();
+
+        let backend = ScriptedBackend::new()
+            .answer("name", Value::String("Jhon".to_string()))
+            .back("ready")
+            .answer("name", Value::String("John".to_string()))
+            .answer("ready", Value::Boolean(true));
+        let mut sm = StateMachine::new(nodes, Context::new(HashMap::new())).with_backend(backend);
+        sm.run(start).unwrap();
+
+        let context = sm.context.get_context();
+        assert_eq!(context.get("name"), Some(&Value::String("John".to_string())));
+        assert_eq!(context.get("greeted"), Some(&Value::Boolean(true)));
+        assert_eq!(context.get("ready"), Some(&Value::Boolean(true)));
+        assert_eq!(
+            sm.history.iter().map(|h| h.key.as_str()).collect::<Vec<_>>(),
+            vec!["name", "ready"]
+        );
+    }
+
+    #[test]
+    fn test_state_machine_back_to_first_prompt() {
+        let content = r#"
+            start = "name"
+
+            [state.name]
+            type = "text"
+            name = "name"
+            message = "What is your name?"
+            to = "age"
+
+            [state.age]
+            type = "integer"
+            name = "age"
+            message = "How old are you?"
+            to = "ready"
+
+            [state.ready]
+            type = "confirm"
+            name = "ready"
+            message = "Ready?"
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+        let (start, nodes) = load(content).unwrap();
+
+        // Going back past the first prompt asks it again.
+        let backend = ScriptedBackend::new()
+            .back("name")
+            .answer("name", Value::String("Jhon".to_string()))
+            .answer("age", Value::Number(20.0))
+            .back("ready")
+            .back("age")
+            .back("name")
+            .answer("name", Value::String("John".to_string()))
+            .answer("age", Value::Number(21.0))
+            .answer("ready", Value::Boolean(true));
+        let mut sm = StateMachine::new(nodes, Context::new(HashMap::new())).with_backend(backend);
+        sm.run(start).unwrap();
+
+        let context = sm.context.get_context();
+        assert_eq!(context.get("name"), Some(&Value::String("John".to_string())));
+        assert_eq!(context.get("age"), Some(&Value::Number(21.0)));
+        assert_eq!(
+            sm.path,
+            vec!["name", "name", "age", "ready", "age", "name", "name", "age", "ready", "end"]
+        );
+    }
+
+    #[test]
+    fn test_state_machine_missing_input() {
+        let content = r#"
+            start = "name"
+
+            [state.name]
+            type = "text"
+            name = "name"
+            message = "What is your name?"
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+        let (start, nodes) = load(content).unwrap();
+
+        let mut sm = StateMachine::new(nodes, Context::new(HashMap::new()))
+            .with_backend(ScriptedBackend::new());
+        assert_eq!(
+            sm.run(start),
+            Err(Error::MissingAnswer(
+                "state 'name' (variable 'name')".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_state_machine_defaults() {
+        let content = r#"
+            start = "name"
+
+            [state.name]
+            type = "text"
+            name = "name"
+            message = "What is your name?"
+            default = "$args.user"
+            to = "ready"
+
+            [state.ready]
+            type = "confirm"
+            name = "ready"
+            message = "Ready?"
+            default = "true"
+            to = "env"
+
+            [state.env]
+            type = "select"
+            name = "env"
+            message = "Environment?"
+            options = ["dev", "stg", "prod"]
+            default = "'stg'"
+            to = "features"
+
+            [state.features]
+            type = "multi_select"
+            name = "features"
+            message = "Features?"
+            options = ["logging", "metrics", "backup"]
+            default = "['backup', 'logging']"
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+        let (start, nodes) = load(content).unwrap();
+
+        let args = HashMap::from([("user".to_string(), Value::String("John".to_string()))]);
+        let backend = ScriptedBackend::new().answer("ready", Value::Boolean(false));
+        let mut sm = StateMachine::new(nodes, Context::new(args)).with_backend(backend);
+        sm.run(start).unwrap();
+
+        let context = sm.context.get_context();
+        assert_eq!(context.get("name"), Some(&Value::String("John".to_string())));
+        assert_eq!(context.get("ready"), Some(&Value::Boolean(false)));
+        assert_eq!(context.get("env"), Some(&Value::String("stg".to_string())));
+        assert_eq!(
+            context.get("features"),
+            Some(&Value::Array(vec![
+                Value::String("logging".to_string()),
+                Value::String("backup".to_string()),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_state_machine_validate() {
+        let content = r#"
+            start = "age"
+
+            [state.age]
+            type = "text"
+            name = "age"
+            message = "How old are you?"
+            validate = "is_number($_) && $_ >= 0"
+            error_message = "'${$_}' is not an age"
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+        let (start, nodes) = load(content).unwrap();
+
+        let backend = ScriptedBackend::new()
+            .answer("age", Value::String("abc".to_string()))
+            .answer("age", Value::String("20".to_string()));
+        let mut sm = StateMachine::new(nodes.clone(), Context::new(HashMap::new()))
+            .with_backend(backend);
+        sm.run(start.clone()).unwrap();
+        assert_eq!(
+            sm.context.get_context().get("age"),
+            Some(&Value::String("20".to_string()))
+        );
+
+        let backend = ScriptedBackend::new().answer("age", Value::String("-1".to_string()));
+        let mut sm = StateMachine::new(nodes, Context::new(HashMap::new())).with_backend(backend);
+        assert_eq!(
+            sm.run(start),
+            Err(Error::InvalidAnswer(
+                "state 'age': '-1' is not an age".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_state_machine_number() {
+        let content = r#"
+            start = "count"
+
+            [state.count]
+            type = "integer"
+            name = "count"
+            message = "How many?"
+            min = "$args.min"
+            max = "10"
+            step = "2"
+            to = "ratio"
+
+            [state.ratio]
+            type = "number"
+            name = "ratio"
+            message = "Ratio?"
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+        let (start, nodes) = load(content).unwrap();
+        let args = HashMap::from([("min".to_string(), Value::Number(1.0))]);
+
+        let backend = ScriptedBackend::new()
+            .answer("count", Value::String("1.5".to_string()))
+            .answer("count", Value::String("0".to_string()))
+            .answer("count", Value::String("12".to_string()))
+            .answer("count", Value::String("4".to_string()))
+            .answer("count", Value::Number(5.0))
+            .answer("ratio", Value::String("0.25".to_string()));
+        let mut sm = StateMachine::new(nodes.clone(), Context::new(args.clone()))
+            .with_backend(backend);
+        sm.run(start.clone()).unwrap();
+
+        let context = sm.context.get_context();
+        assert_eq!(context.get("count"), Some(&Value::Number(5.0)));
+        assert_eq!(context.get("ratio"), Some(&Value::Number(0.25)));
+
+        let cases = [
+            ("1.5", "'1.5' is not an integer"),
+            ("0", "Must be at least 1"),
+            ("12", "Must be at most 10"),
+            ("4", "Must be 1 plus a multiple of 2"),
+        ];
+        for (answer, reason) in cases {
+            let backend = ScriptedBackend::new().answer("count", Value::String(answer.to_string()));
+            let mut sm = StateMachine::new(nodes.clone(), Context::new(args.clone()))
+                .with_backend(backend);
+            assert_eq!(
+                sm.run(start.clone()),
+                Err(Error::InvalidAnswer(format!("state 'count': {}", reason)))
+            );
+        }
+    }
+
+    #[test]
+    fn test_state_machine_resume() {
+        let content = r#"
+            start = "name"
+
+            [state.name]
+            type = "text"
+            name = "name"
+            message = "What is your name?"
+            to = "age"
+
+            [state.age]
+            type = "integer"
+            name = "age"
+            message = "How old are you?"
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+        let (start, nodes) = load(content).unwrap();
+        let path = std::env::temp_dir().join(format!("promptoml-resume-{}.json", std::process::id()));
+        let session = Session::new(&path, content);
+
+        let backend = ScriptedBackend::new().answer("name", Value::String("John".to_string()));
+        let mut sm = StateMachine::new(nodes.clone(), Context::new(HashMap::new()))
+            .with_backend(backend)
+            .with_session(session.clone());
+        assert!(matches!(sm.run(start), Err(Error::MissingAnswer(_))));
+
+        let checkpoint = session.load().unwrap();
+        assert_eq!(checkpoint.current, "age");
+
+        let backend = ScriptedBackend::new().answer("age", Value::Number(20.0));
+        let mut sm = StateMachine::new(nodes, Context::new(HashMap::new()))
+            .with_backend(backend)
+            .with_session(session);
+        sm.resume(checkpoint.clone()).unwrap();
+
+        let context = sm.context.get_context();
+        assert_eq!(context.get("name"), Some(&Value::String("John".to_string())));
+        assert_eq!(context.get("age"), Some(&Value::Number(20.0)));
+        assert_eq!(sm.path, vec!["name", "age", "end"]);
+        assert!(!path.exists());
+
+        // A state of the session that is gone, e.g. from an edited include,
+        // is refused instead of being run.
+        let (_, mut nodes) = load(content).unwrap();
+        nodes.remove("name");
+        let mut sm = StateMachine::new(nodes, Context::new(HashMap::new())).with_backend(ScriptedBackend::new());
+        assert_eq!(
+            sm.resume(checkpoint),
+            Err(Error::SessionError(
+                "state 'name' of the session no longer exists in the wizard".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_state_machine_call() {
+        let dir = std::env::temp_dir().join(format!("promptoml-call-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("region.toml"),
+            r#"
+                start = "region"
+
+                [state.region]
+                type = "select"
+                name = "region"
+                message = "Region for ${$args.profile}?"
+                options = ["'us-east-1'", "'ap-northeast-1'"]
+                default = "$args.region"
+                to = "confirm"
+
+                [state.confirm]
+                type = "confirm"
+                name = "confirmed"
+                message = "OK?"
+                to = "end"
+
+                [state.end]
+                type = "done"
+            "#,
+        )
+        .unwrap();
+
+        let content = r#"
+            start = "profile"
+
+            [state.profile]
+            type = "text"
+            name = "profile"
+            message = "Profile?"
+            to = "pick"
+
+            [state.pick]
+            type = "call"
+            name = "aws"
+            wizard = "region.toml"
+            args = { profile = "$profile", region = "'ap-northeast-1'" }
+            returns = ["region"]
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+        let (start, nodes) = load_from(content, &dir.join("wizard.toml")).unwrap();
+
+        let backend = ScriptedBackend::new()
+            .answer("profile", Value::String("prod".to_string()))
+            .answer("confirm", Value::Boolean(true));
+        let mut sm = StateMachine::new(nodes, Context::new(HashMap::new())).with_backend(backend);
+        sm.run(start).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            sm.context.get_context().get("aws"),
+            Some(&Value::Object(HashMap::from([(
+                "region".to_string(),
+                Value::String("ap-northeast-1".to_string())
+            )])))
+        );
+        assert_eq!(sm.path, vec!["profile", "pick", "end"]);
+    }
+
+    #[test]
+    fn test_state_machine_call_back() {
+        let dir = std::env::temp_dir().join(format!("promptoml-call-back-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("region.toml"),
+            r#"
+                start = "region"
+
+                [state.region]
+                type = "text"
+                name = "region"
+                message = "Region?"
+                to = "end"
+
+                [state.end]
+                type = "done"
+            "#,
+        )
+        .unwrap();
+
+        let content = r#"
+            start = "profile"
+
+            [state.profile]
+            type = "text"
+            name = "profile"
+            message = "Profile?"
+            to = "pick"
+
+            [state.pick]
+            type = "call"
+            name = "aws"
+            wizard = "region.toml"
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+        let (start, nodes) = load_from(content, &dir.join("wizard.toml")).unwrap();
+
+        // Going back from the first prompt of the called wizard returns to
+        // the prompt before the call.
+        let backend = ScriptedBackend::new()
+            .answer("profile", Value::String("dev".to_string()))
+            .back("region")
+            .answer("profile", Value::String("prod".to_string()))
+            .answer("region", Value::String("us-east-1".to_string()));
+        let mut sm = StateMachine::new(nodes, Context::new(HashMap::new())).with_backend(backend);
+        sm.run(start).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let context = sm.context.get_context();
+        assert_eq!(context.get("profile"), Some(&Value::String("prod".to_string())));
+        assert_eq!(
+            context.get("aws"),
+            Some(&Value::Object(HashMap::from([(
+                "region".to_string(),
+                Value::String("us-east-1".to_string())
+            )])))
+        );
+        assert_eq!(sm.path, vec!["profile", "pick", "profile", "pick", "end"]);
+    }
+
+    #[test]
+    fn test_state_machine_call_cycle() {
+        let dir = std::env::temp_dir().join(format!("promptoml-call-cycle-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let content = r#"
+            start = "again"
+
+            [state.again]
+            type = "call"
+            name = "result"
+            wizard = "wizard.toml"
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+        let wizard = dir.join("wizard.toml");
+        std::fs::write(&wizard, content).unwrap();
+        let (start, nodes) = load_from(content, &wizard).unwrap();
+
+        let mut sm = StateMachine::new(nodes, Context::new(HashMap::new()))
+            .with_backend(ScriptedBackend::new())
+            .with_wizard(&wizard);
+        let result = sm.run(start);
+        let wizard = wizard.canonicalize().unwrap().display().to_string();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            result,
+            Err(Error::CallError(format!("call cycle: {} -> {}", wizard, wizard)))
+        );
+    }
+
+    #[test]
+    fn test_state_machine_labeled_options() {
+        let content = r#"
+            start = "operation"
+
+            [state.operation]
+            type = "select"
+            name = "operation"
+            message = "Operation?"
+            options = [
+                { label = "Edit bucket policy", value = "'edit_bucket_policy'" },
+                { label = "Delete bucket", value = "$args.delete", description = "Deletes every object" },
+            ]
+            to = "targets"
+
+            [state.targets]
+            type = "multi_select"
+            name = "targets"
+            message = "Targets?"
+            options = [
+                { label = "Production", value = "1" },
+                { label = "Staging", value = "2" },
+                "'dev'",
+            ]
+            default = "[2]"
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+        let (start, nodes) = load(content).unwrap();
+        let delete = Value::Object(HashMap::from([("force".to_string(), Value::Boolean(true))]));
+        let args = HashMap::from([("delete".to_string(), delete.clone())]);
+
+        let backend = ScriptedBackend::new().answer("operation", Value::String("Edit bucket policy".to_string()));
+        let mut sm = StateMachine::new(nodes.clone(), Context::new(args.clone())).with_backend(backend);
+        sm.run(start.clone()).unwrap();
+
+        let context = sm.context.get_context();
+        assert_eq!(
+            context.get("operation"),
+            Some(&Value::String("edit_bucket_policy".to_string()))
+        );
+        assert_eq!(context.get("targets"), Some(&Value::Array(vec![Value::Number(2.0)])));
+
+        let backend = ScriptedBackend::new()
+            .answer("operation", Value::String("Delete bucket".to_string()))
+            .answer(
+                "targets",
+                Value::Array(vec![Value::Number(1.0), Value::String("dev".to_string())]),
+            );
+        let mut sm = StateMachine::new(nodes, Context::new(args)).with_backend(backend);
+        sm.run(start).unwrap();
+
+        let context = sm.context.get_context();
+        assert_eq!(context.get("operation"), Some(&delete));
+        assert_eq!(
+            context.get("targets"),
+            Some(&Value::Array(vec![Value::Number(1.0), Value::String("dev".to_string())]))
+        );
+    }
+
+    #[test]
+    fn test_state_machine_options_from() {
+        let content = r#"
+            start = "bucket"
+
+            [state.bucket]
+            type = "select"
+            name = "bucket"
+            message = "Bucket?"
+            options_from = "$args.buckets"
+            label = "name"
+            to = "regions"
+
+            [state.regions]
+            type = "multi_select"
+            name = "regions"
+            message = "Regions?"
+            options_from = "$args.buckets"
+            label = "$_.region"
+            default = "$args.buckets"
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+        let (start, nodes) = load(content).unwrap();
+
+        let bucket = |name: &str, region: &str| {
+            Value::Object(HashMap::from([
+                ("name".to_string(), Value::String(name.to_string())),
+                ("region".to_string(), Value::String(region.to_string())),
+            ]))
+        };
+        let buckets = vec![bucket("logs", "us-east-1"), bucket("assets", "ap-northeast-1")];
+        let args = HashMap::from([("buckets".to_string(), Value::Array(buckets.clone()))]);
+
+        let backend = ScriptedBackend::new().answer("bucket", Value::String("assets".to_string()));
+        let mut sm = StateMachine::new(nodes, Context::new(args)).with_backend(backend);
+        sm.run(start).unwrap();
+
+        let context = sm.context.get_context();
+        assert_eq!(context.get("bucket"), Some(&buckets[1]));
+        assert_eq!(context.get("regions"), Some(&Value::Array(buckets)));
+    }
+
+    #[test]
+    fn test_state_machine_review() {
+        let content = r#"
+            start = "name"
+
+            [state.name]
+            type = "text"
+            name = "name"
+            message = "What is your name?"
+            to = "age"
+
+            [state.age]
+            type = "integer"
+            name = "age"
+            message = "How old are you?"
+            to = "check"
+
+            [state.check]
+            type = "condition"
+            condition = "$age >= 20"
+            branches = { true = "drink", false = "review" }
+
+            [state.drink]
+            type = "select"
+            name = "drink"
+            message = "Drink?"
+            options = ["'beer'", "'tea'"]
+            to = "review"
+
+            [state.review]
+            type = "review"
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+        let (start, nodes) = load(content).unwrap();
+
+        let backend = ScriptedBackend::new()
+            .answer("name", Value::String("John".to_string()))
+            .answer("age", Value::Number(15.0))
+            .answer("review", Value::String("How old are you?".to_string()))
+            .answer("age", Value::Number(20.0))
+            .answer("drink", Value::String("tea".to_string()))
+            .answer("review", Value::String("name".to_string()))
+            .answer("name", Value::String("Jane".to_string()));
+        let mut sm = StateMachine::new(nodes, Context::new(HashMap::new())).with_backend(backend);
+        sm.run(start).unwrap();
+
+        let context = sm.context.get_context();
+        assert_eq!(context.get("name"), Some(&Value::String("Jane".to_string())));
+        assert_eq!(context.get("age"), Some(&Value::Number(20.0)));
+        assert_eq!(context.get("drink"), Some(&Value::String("tea".to_string())));
+        assert_eq!(
+            sm.path,
+            vec![
+                "name", "age", "check", "review",
+                "age", "check", "drink", "review",
+                "name", "age", "check", "drink", "review", "end",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_state_machine_review_drops_abandoned_answers() {
+        let content = r#"
+            start = "age"
+
+            [state.age]
+            type = "integer"
+            name = "age"
+            message = "How old are you?"
+            to = "check"
+
+            [state.check]
+            type = "condition"
+            condition = "$age >= 20"
+            branches = { true = "drink", false = "review" }
+
+            [state.drink]
+            type = "select"
+            name = "drink"
+            message = "Drink?"
+            options = ["'beer'", "'tea'"]
+            to = "review"
+
+            [state.review]
+            type = "review"
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+        let (start, nodes) = load(content).unwrap();
+
+        let backend = ScriptedBackend::new()
+            .answer("age", Value::Number(20.0))
+            .answer("drink", Value::String("beer".to_string()))
+            .answer("review", Value::String("age".to_string()))
+            .answer("age", Value::Number(15.0));
+        let mut sm = StateMachine::new(nodes, Context::new(HashMap::new())).with_backend(backend);
+        sm.run(start).unwrap();
+
+        let context = sm.context.get_context();
+        assert_eq!(context.get("age"), Some(&Value::Number(15.0)));
+        assert_eq!(context.get("drink"), None);
+    }
+
+    #[test]
+    fn test_state_machine_review_replays_answers() {
+        let content = r#"
+            start = "label"
+
+            [state.label]
+            type = "text"
+            name = "label"
+            message = "Label?"
+            to = "limit"
+
+            [state.limit]
+            type = "integer"
+            name = "limit"
+            message = "Limit?"
+            to = "n"
+
+            [state.n]
+            type = "integer"
+            name = "n"
+            message = "How many?"
+            max = "$limit"
+            to = "inc"
+
+            [state.inc]
+            type = "set"
+            name = "n"
+            value = "$n * 10"
+            to = "review"
+
+            [state.review]
+            type = "review"
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+        let (start, nodes) = load(content).unwrap();
+
+        // The answer given to `n` is replayed, not what `inc` made of it.
+        let backend = ScriptedBackend::new()
+            .answer("label", Value::String("a".to_string()))
+            .answer("limit", Value::Number(10.0))
+            .answer("n", Value::Number(2.0))
+            .answer("review", Value::String("label".to_string()))
+            .answer("label", Value::String("b".to_string()));
+        let mut sm = StateMachine::new(nodes.clone(), Context::new(HashMap::new())).with_backend(backend);
+        sm.run(start.clone()).unwrap();
+        assert_eq!(sm.context.get_context().get("n"), Some(&Value::Number(20.0)));
+
+        // An answer the prompt now rejects is asked again.
+        let backend = ScriptedBackend::new()
+            .answer("label", Value::String("a".to_string()))
+            .answer("limit", Value::Number(10.0))
+            .answer("n", Value::Number(8.0))
+            .answer("review", Value::String("limit".to_string()))
+            .answer("limit", Value::Number(3.0))
+            .answer("n", Value::Number(2.0));
+        let mut sm = StateMachine::new(nodes, Context::new(HashMap::new())).with_backend(backend);
+        sm.run(start).unwrap();
+
+        let context = sm.context.get_context();
+        assert_eq!(context.get("limit"), Some(&Value::Number(3.0)));
+        assert_eq!(context.get("n"), Some(&Value::Number(20.0)));
+    }
+
+    #[test]
+    fn test_state_machine_review_back() {
+        let content = r#"
+            start = "name"
+
+            [state.name]
+            type = "text"
+            name = "name"
+            message = "What is your name?"
+            to = "age"
+
+            [state.age]
+            type = "integer"
+            name = "age"
+            message = "How old are you?"
+            to = "drink"
+
+            [state.drink]
+            type = "select"
+            name = "drink"
+            message = "Drink?"
+            options = ["'beer'", "'tea'"]
+            to = "review"
+
+            [state.review]
+            type = "review"
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+        let (start, nodes) = load(content).unwrap();
+
+        // After answering `name` again from the review, `age` and `drink`
+        // keep their answers, but going back still reaches each of them.
+        let backend = ScriptedBackend::new()
+            .answer("name", Value::String("John".to_string()))
+            .answer("age", Value::Number(20.0))
+            .answer("drink", Value::String("tea".to_string()))
+            .answer("review", Value::String("name".to_string()))
+            .answer("name", Value::String("Jane".to_string()))
+            .back("review")
+            .back("drink")
+            .answer("age", Value::Number(30.0))
+            .answer("drink", Value::String("beer".to_string()));
+        let mut sm = StateMachine::new(nodes, Context::new(HashMap::new())).with_backend(backend);
+        sm.run(start).unwrap();
+
+        let context = sm.context.get_context();
+        assert_eq!(context.get("name"), Some(&Value::String("Jane".to_string())));
+        assert_eq!(context.get("age"), Some(&Value::Number(30.0)));
+        assert_eq!(context.get("drink"), Some(&Value::String("beer".to_string())));
+        assert_eq!(
+            sm.path,
+            vec![
+                "name", "age", "drink", "review",
+                "name", "age", "drink", "review",
+                "drink", "age", "drink", "review", "end",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_state_machine_collect() {
+        let content = r#"
+            start = "tags"
+
+            [state.tags]
+            type = "collect"
+            name = "tags"
+            body = "tag"
+            to = "ports"
+
+            [state.tag]
+            type = "text"
+            name = "tag"
+            message = "Tag (empty to finish)"
+            to = "tags"
+
+            [state.ports]
+            type = "collect"
+            name = "ports"
+            body = "port"
+            max = "3"
+            confirm = "Add another port?"
+            to = "end"
+
+            [state.port]
+            type = "integer"
+            name = "port"
+            message = "Port"
+            to = "ports"
+
+            [state.end]
+            type = "done"
+        "#;
+        let (start, nodes) = load(content).unwrap();
+
+        let backend = ScriptedBackend::new()
+            .answer("tag", Value::String("web".to_string()))
+            .answer("tag", Value::String("db".to_string()))
+            .answer("tag", Value::String("".to_string()))
+            .answer("port", Value::Number(80.0))
+            .answer("ports", Value::Boolean(true))
+            .answer("port", Value::Number(8080.0))
+            .back("ports")
+            .answer("port", Value::Number(443.0))
+            .answer("ports", Value::Boolean(false));
+        let mut sm = StateMachine::new(nodes, Context::new(HashMap::new())).with_backend(backend);
+        sm.run(start).unwrap();
+
+        let context = sm.context.get_context();
+        assert_eq!(
+            context.get("tags"),
+            Some(&Value::Array(vec![
+                Value::String("web".to_string()),
+                Value::String("db".to_string()),
+            ]))
+        );
+        assert_eq!(
+            context.get("ports"),
+            Some(&Value::Array(vec![Value::Number(80.0), Value::Number(443.0)]))
+        );
+        assert_eq!(context.get("tag"), None);
+        assert_eq!(context.get("port"), None);
+        assert_eq!(sm.context.loop_iteration("ports"), None);
+    }
+
+    #[test]
+    fn test_state_machine_collect_back_and_review() {
+        let content = r#"
+            start = "tags"
+
+            [state.tags]
+            type = "collect"
+            name = "tags"
+            body = "tag"
+            to = "review"
+
+            [state.tag]
+            type = "text"
+            name = "tag"
+            message = "Tag (empty to finish)"
+            to = "tags"
+
+            [state.review]
+            type = "review"
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+        let (start, nodes) = load(content).unwrap();
+
+        // Going back inside the body undoes the previous round, and picking
+        // the body in the review reopens the loop after the rounds kept.
+        let backend = ScriptedBackend::new()
+            .answer("tag", Value::String("a".to_string()))
+            .answer("tag", Value::String("b".to_string()))
+            .back("tag")
+            .answer("tag", Value::String("c".to_string()))
+            .answer("tag", Value::String("".to_string()))
+            .answer("review", Value::String("tag".to_string()))
+            .answer("tag", Value::String("d".to_string()))
+            .answer("tag", Value::String("".to_string()));
+        let mut sm = StateMachine::new(nodes, Context::new(HashMap::new())).with_backend(backend);
+        sm.run(start).unwrap();
+
+        let context = sm.context.get_context();
+        assert_eq!(
+            context.get("tags"),
+            Some(&Value::Array(vec![
+                Value::String("a".to_string()),
+                Value::String("c".to_string()),
+                Value::String("d".to_string()),
+            ]))
+        );
+        assert_eq!(sm.context.loop_iteration("tags"), None);
+        assert_eq!(
+            sm.path,
+            vec![
+                "tags", "tag", "tags", "tag", "tags", "tag",
+                "tag", "tags", "tag", "tags", "review",
+                "tag", "tags", "tag", "tags", "review", "end",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_state_machine_foreach() {
+        let content = r#"
+            start = "services"
+
+            [state.services]
+            type = "multi_select"
+            name = "services"
+            message = "Services"
+            options = ["'web'", "'db'", "'cache'"]
+            to = "ports"
+
+            [state.ports]
+            type = "foreach"
+            name = "ports"
+            over = "$services"
+            as = "service"
+            body = "port"
+            keyed = true
+            to = "settings"
+
+            [state.port]
+            type = "integer"
+            name = "port"
+            message = "Port for ${$service}?"
+            to = "ports"
+
+            [state.settings]
+            type = "foreach"
+            name = "settings"
+            over = "$ports"
+            body = "position"
+            collect = ["position", "port"]
+            to = "end"
+
+            [state.position]
+            type = "set"
+            name = "position"
+            value = "$index"
+            to = "port_of"
+
+            [state.port_of]
+            type = "set"
+            name = "port"
+            value = "$ports[$item]"
+            to = "settings"
+
+            [state.end]
+            type = "done"
+        "#;
+        let (start, nodes) = load(content).unwrap();
+
+        let backend = ScriptedBackend::new()
+            .answer(
+                "services",
+                Value::Array(vec![
+                    Value::String("web".to_string()),
+                    Value::String("db".to_string()),
+                ]),
+            )
+            .answer("port", Value::Number(80.0))
+            .answer("port", Value::Number(5432.0));
+        let mut sm = StateMachine::new(nodes, Context::new(HashMap::new())).with_backend(backend);
+        sm.run(start).unwrap();
+
+        let context = sm.context.get_context();
+        assert_eq!(
+            context.get("ports"),
+            Some(&Value::Object(HashMap::from([
+                ("web".to_string(), Value::Number(80.0)),
+                ("db".to_string(), Value::Number(5432.0)),
+            ])))
+        );
+        let setting = |position: f64, port: f64| {
+            Value::Object(HashMap::from([
+                ("position".to_string(), Value::Number(position)),
+                ("port".to_string(), Value::Number(port)),
+            ]))
+        };
+        assert_eq!(
+            context.get("settings"),
+            Some(&Value::Object(HashMap::from([
+                ("db".to_string(), setting(0.0, 5432.0)),
+                ("web".to_string(), setting(1.0, 80.0)),
+            ])))
+        );
+        for name in ["service", "item", "index", "port", "position"] {
+            assert_eq!(context.get(name), None);
+        }
+    }
+
+    #[test]
+    fn test_state_machine_when() {
+        let content = r#"
+            start = "advanced"
+
+            [state.advanced]
+            type = "confirm"
+            name = "advanced"
+            message = "Configure advanced settings?"
+            to = "port"
+
+            [state.port]
+            type = "integer"
+            name = "port"
+            message = "Port"
+            default = "8080"
+            when = "$advanced"
+            to = "comment"
+
+            [state.comment]
+            type = "text"
+            name = "comment"
+            message = "Comment"
+            when = "$advanced"
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+        let (start, nodes) = load(content).unwrap();
+
+        let backend = ScriptedBackend::new().answer("advanced", Value::Boolean(false));
+        let mut sm = StateMachine::new(nodes.clone(), Context::new(HashMap::new())).with_backend(backend);
+        sm.run(start.clone()).unwrap();
+
+        let context = sm.context.get_context();
+        assert_eq!(context.get("port"), Some(&Value::Number(8080.0)));
+        assert_eq!(context.get("comment"), None);
+        assert_eq!(sm.path, vec!["advanced", "port", "comment", "end"]);
+        assert_eq!(sm.skipped, vec!["port", "comment"]);
+
+        let backend = ScriptedBackend::new()
+            .answer("advanced", Value::Boolean(true))
+            .answer("port", Value::String("3000".to_string()))
+            .answer("comment", Value::String("staging".to_string()));
+        let mut sm = StateMachine::new(nodes, Context::new(HashMap::new())).with_backend(backend);
+        sm.run(start).unwrap();
+
+        assert_eq!(sm.context.get_context().get("port"), Some(&Value::Number(3000.0)));
+        assert!(sm.skipped.is_empty());
+    }
+
+    #[test]
+    fn test_state_machine_when_converts_defaults() {
+        let content = r#"
+            start = "region"
+
+            [state.region]
+            type = "select"
+            name = "region"
+            message = "Region?"
+            options = [
+                { label = "Tokyo", value = "'ap-northeast-1'" },
+                { label = "Virginia", value = "'us-east-1'" },
+            ]
+            default = "'Tokyo'"
+            when = "false"
+            to = "zones"
+
+            [state.zones]
+            type = "multi_select"
+            name = "zones"
+            message = "Zones?"
+            options = [{ label = "A", value = "'a'" }, { label = "B", value = "'b'" }]
+            default = "['B']"
+            when = "false"
+            to = "port"
+
+            [state.port]
+            type = "integer"
+            name = "port"
+            message = "Port"
+            default = "'8080'"
+            when = "false"
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+        let (start, nodes) = load(content).unwrap();
+
+        let mut sm = StateMachine::new(nodes, Context::new(HashMap::new())).with_backend(ScriptedBackend::new());
+        sm.run(start).unwrap();
+
+        let context = sm.context.get_context();
+        assert_eq!(context.get("region"), Some(&Value::String("ap-northeast-1".to_string())));
+        assert_eq!(context.get("zones"), Some(&Value::Array(vec![Value::String("b".to_string())])));
+        assert_eq!(context.get("port"), Some(&Value::Number(8080.0)));
+        assert_eq!(sm.skipped, vec!["region", "zones", "port"]);
+
+        let content = r#"
+            start = "port"
+
+            [state.port]
+            type = "integer"
+            name = "port"
+            message = "Port"
+            default = "80"
+            min = "1024"
+            when = "false"
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+        let (start, nodes) = load(content).unwrap();
+
+        let mut sm = StateMachine::new(nodes, Context::new(HashMap::new())).with_backend(ScriptedBackend::new());
+        assert_eq!(
+            sm.run(start),
+            Err(Error::TypeError(
+                "Default '80' of a skipped prompt is rejected: Must be at least 1024".to_string()
+            ))
+        );
     }
 }
@@ -0,0 +1,66 @@
+use crate::{
+    error::{Error, Result},
+    eval::{eval, Context},
+    parser::{Expr, Value},
+};
+
+/// Goes to the first case whose guard holds, or to `default` when none
+/// does.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Switch {
+    /// Guards paired with the state to enter, in the order they are tried.
+    pub cases: Vec<(Expr, String)>,
+    pub default: String,
+}
+
+impl Switch {
+    pub fn eval(&self, context: &Context) -> Result<String> {
+        for (when, to) in &self.cases {
+            match eval(when, context)? {
+                Value::Boolean(true) => return Ok(to.clone()),
+                Value::Boolean(false) => {}
+                value => {
+                    return Err(Error::TypeError(format!(
+                        "Switch case must evaluate to a boolean, got {:?}",
+                        value
+                    )))
+                }
+            }
+        }
+        Ok(self.default.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::parser::parse;
+
+    use super::*;
+
+    fn switch() -> Switch {
+        Switch {
+            cases: vec![
+                (parse("$n > 10").unwrap(), "large".to_string()),
+                (parse("$n > 0").unwrap(), "small".to_string()),
+            ],
+            default: "none".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_switch_eval() {
+        let mut context = Context::new(HashMap::new());
+        for (n, expected) in [(20.0, "large"), (5.0, "small"), (0.0, "none")] {
+            context.set_variable("n".to_string(), Value::Number(n));
+            assert_eq!(switch().eval(&context), Ok(expected.to_string()));
+        }
+
+        let not_boolean = Switch {
+            cases: vec![(parse("$n").unwrap(), "large".to_string())],
+            default: "none".to_string(),
+        };
+        assert!(matches!(not_boolean.eval(&context), Err(Error::TypeError(_))));
+    }
+}
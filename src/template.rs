@@ -0,0 +1,188 @@
+use crate::{
+    error::{Error, Result},
+    eval::{eval, Context},
+    parser::{parse, Expr, Value},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Expr(Expr),
+}
+
+/// A string with `${...}` placeholders, each holding an expression that is
+/// evaluated when the template is rendered. `$${` is a literal `${`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    source: String,
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self> {
+        let invalid = |reason: String| Error::InvalidTemplate(format!("{:?}: {}", source, reason));
+
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut rest = source;
+        while let Some(i) = rest.find("${") {
+            if rest[..i].ends_with('$') {
+                text.push_str(&rest[..i - 1]);
+                text.push_str("${");
+                rest = &rest[i + 2..];
+                continue;
+            }
+
+            text.push_str(&rest[..i]);
+            let body = &rest[i + 2..];
+            let end = placeholder_end(body)
+                .ok_or_else(|| invalid(format!("unterminated placeholder '{}'", &rest[i..])))?;
+
+            let inner = body[..end].trim();
+            if inner.is_empty() {
+                return Err(invalid("empty placeholder '${}'".to_string()));
+            }
+            let expr = parse(inner)
+                .map_err(|e| invalid(format!("placeholder '${{{}}}': {}", &body[..end], e)))?;
+
+            if !text.is_empty() {
+                parts.push(Part::Text(std::mem::take(&mut text)));
+            }
+            parts.push(Part::Expr(expr));
+            rest = &body[end + 1..];
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+
+        Ok(Self {
+            source: source.to_string(),
+            parts,
+        })
+    }
+
+    /// The template as written in the wizard file.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn expressions(&self) -> impl Iterator<Item = &Expr> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Expr(expr) => Some(expr),
+            Part::Text(_) => None,
+        })
+    }
+
+    pub fn render(&self, context: &Context) -> Result<String> {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => out.push_str(text),
+                Part::Expr(expr) => out.push_str(&to_text(&eval(expr, context)?)),
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Finds the `}` closing a placeholder, skipping over quoted strings.
+fn placeholder_end(body: &str) -> Option<usize> {
+    let mut quoted = false;
+    for (i, c) in body.char_indices() {
+        match c {
+            '\'' => quoted = !quoted,
+            '}' if !quoted => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// How a value is written into rendered text.
+pub fn to_text(value: &Value) -> String {
+    match value {
+        Value::String(s) | Value::Symbol(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        Value::Boolean(b) => b.to_string(),
+        Value::Null => "null".to_string(),
+        Value::Array(items) => items.iter().map(to_text).collect::<Vec<_>>().join(", "),
+        Value::Object(o) => {
+            let mut entries: Vec<_> = o.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            let entries: Vec<_> = entries
+                .into_iter()
+                .map(|(k, v)| format!("{}: {}", k, to_text(v)))
+                .collect();
+            format!("{{{}}}", entries.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn context() -> Context {
+        let mut context = Context::new(HashMap::from([(
+            "env".to_string(),
+            Value::String("prod".to_string()),
+        )]));
+        context.set_variable("name".to_string(), Value::String("John".to_string()));
+        context.set_variable(
+            "numbers".to_string(),
+            Value::Array(vec![Value::Number(1.0), Value::Number(2.5)]),
+        );
+        context
+    }
+
+    #[test]
+    fn test_render() {
+        let cases = [
+            ("Hello, ${$name}!", "Hello, John!"),
+            ("${ $args.env }/${$name}", "prod/John"),
+            ("Numbers: ${$numbers}", "Numbers: 1, 2.5"),
+            ("${1 + 2 * 3} items", "7 items"),
+            ("Closing: ${'}'}", "Closing: }"),
+            ("Literal $${name} and $name", "Literal ${name} and $name"),
+            ("No placeholders", "No placeholders"),
+        ];
+
+        for (source, expected) in cases {
+            let template = Template::parse(source).unwrap();
+            assert_eq!(template.render(&context()).unwrap(), expected, "{}", source);
+            assert_eq!(template.source(), source);
+        }
+    }
+
+    #[test]
+    fn test_render_error() {
+        let template = Template::parse("Hi ${$missing}").unwrap();
+        assert_eq!(
+            template.render(&context()),
+            Err(Error::UndefinedVariable("missing".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        assert_eq!(
+            Template::parse("Hello, ${$name"),
+            Err(Error::InvalidTemplate(
+                "\"Hello, ${$name\": unterminated placeholder '${$name'".to_string()
+            ))
+        );
+        assert_eq!(
+            Template::parse("${ }"),
+            Err(Error::InvalidTemplate(
+                "\"${ }\": empty placeholder '${}'".to_string()
+            ))
+        );
+        assert!(matches!(
+            Template::parse("${$a $b}"),
+            Err(Error::InvalidTemplate(_))
+        ));
+    }
+}
@@ -1,21 +1,50 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use serde::Deserialize;
 use toml::Table;
 
 use crate::{
-    condition::Condition, error::{Error, Result}, goto::Goto, parser::parse, prompt::{
-        ConfirmPrompt, FuzzySelectPrompt, MultiSelectPrompt, PasswordPrompt, PromptType,
-        SelectPrompt, TextPrompt,
-    }, state::{Node, State}
+    call::Call, collect::Collect, condition::Condition, error::{Error, Result}, exec::{Exec, Output}, foreach::Foreach, goto::Goto, switch::Switch, parser::{parse, Expr, Value}, prompt::{
+        Bounds, Choice, ConfirmPrompt, FuzzySelectPrompt, IntegerPrompt, MultiSelectPrompt,
+        NumberPrompt, PasswordPrompt, PromptType, SelectPrompt, TextPrompt, Validation,
+    }, review::Review, state::{Node, State, Target}, template::Template
 };
 
 #[derive(Debug, Deserialize)]
 struct Config {
-    start: String,
+    /// Only optional in included files.
+    start: Option<String>,
+    /// Files whose states are merged in under the file name as namespace,
+    /// relative to this file.
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
     state: HashMap<String, StateConfig>,
 }
 
+/// An entry of `options`: an expression, or a table with a label shown in
+/// place of its value.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum OptionConfig {
+    Expr(String),
+    Item {
+        label: String,
+        value: Option<String>,
+        description: Option<String>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct CaseConfig {
+    when: String,
+    to: String,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type")]
 enum StateConfig {
@@ -23,39 +52,86 @@ enum StateConfig {
     Text {
         name: String,
         message: String,
+        default: Option<String>,
+        when: Option<String>,
+        validate: Option<String>,
+        error_message: Option<String>,
         to: String,
     },
     #[serde(rename = "confirm")]
     Confirm {
         name: String,
         message: String,
+        default: Option<String>,
+        when: Option<String>,
         to: String,
     },
     #[serde(rename = "password")]
     Password {
         name: String,
         message: String,
+        default: Option<String>,
+        when: Option<String>,
+        validate: Option<String>,
+        error_message: Option<String>,
+        to: String,
+    },
+    #[serde(rename = "number")]
+    Number {
+        name: String,
+        message: String,
+        default: Option<String>,
+        when: Option<String>,
+        min: Option<String>,
+        max: Option<String>,
+        step: Option<String>,
+        to: String,
+    },
+    #[serde(rename = "integer")]
+    Integer {
+        name: String,
+        message: String,
+        default: Option<String>,
+        when: Option<String>,
+        min: Option<String>,
+        max: Option<String>,
+        step: Option<String>,
         to: String,
     },
     #[serde(rename = "select")]
     Select {
         name: String,
         message: String,
-        options: Vec<String>,
+        #[serde(default)]
+        options: Vec<OptionConfig>,
+        options_from: Option<String>,
+        label: Option<String>,
+        default: Option<String>,
+        when: Option<String>,
         to: String,
     },
     #[serde(rename = "multi_select")]
     MultiSelect {
         name: String,
         message: String,
-        options: Vec<String>,
+        #[serde(default)]
+        options: Vec<OptionConfig>,
+        options_from: Option<String>,
+        label: Option<String>,
+        default: Option<String>,
+        when: Option<String>,
         to: String,
     },
     #[serde(rename = "fuzzy_select")]
     FuzzySelect {
         name: String,
         message: String,
-        options: Vec<String>,
+        #[serde(default)]
+        options: Vec<OptionConfig>,
+        options_from: Option<String>,
+        label: Option<String>,
+        default: Option<String>,
+        when: Option<String>,
         to: String,
     },
     #[serde(rename = "condition")]
@@ -63,6 +139,11 @@ enum StateConfig {
         condition: String,
         branches: Table,
     },
+    #[serde(rename = "switch")]
+    Switch {
+        cases: Vec<CaseConfig>,
+        default: String,
+    },
     #[serde(rename = "goto")]
     Goto {
         target: String,
@@ -78,41 +159,331 @@ enum StateConfig {
         name: String,
         to: String,
     },
+    #[serde(rename = "call")]
+    Call {
+        name: String,
+        wizard: String,
+        #[serde(default)]
+        args: HashMap<String, String>,
+        returns: Option<Vec<String>>,
+        to: String,
+    },
+    #[serde(rename = "exec")]
+    Exec {
+        name: String,
+        command: Vec<String>,
+        cwd: Option<String>,
+        #[serde(default)]
+        env: HashMap<String, String>,
+        /// Seconds.
+        timeout: Option<u64>,
+        #[serde(default)]
+        output: Output,
+        on_success: String,
+        on_failure: Option<String>,
+    },
+    #[serde(rename = "review")]
+    Review {
+        message: Option<String>,
+        to: String,
+    },
+    #[serde(rename = "collect")]
+    Collect {
+        name: String,
+        body: String,
+        item: Option<String>,
+        max: Option<String>,
+        confirm: Option<String>,
+        to: String,
+    },
+    #[serde(rename = "foreach")]
+    Foreach {
+        name: String,
+        over: String,
+        #[serde(rename = "as")]
+        item: Option<String>,
+        index: Option<String>,
+        body: String,
+        collect: Option<Vec<String>>,
+        #[serde(default)]
+        keyed: bool,
+        to: String,
+    },
     #[serde(rename = "done")]
     Done,
 }
 
 pub fn load(content: &str) -> Result<(String, HashMap<String, Node>)> {
+    load_from(content, Path::new(""))
+}
+
+/// Loads a wizard read from `path`, resolving the files it refers to
+/// relative to the directory of `path`.
+pub fn load_from(content: &str, path: &Path) -> Result<(String, HashMap<String, Node>)> {
     let config: Config = toml::from_str(content).map_err(|e| Error::ParseError(e.to_string()))?;
+    let start = config
+        .start
+        .clone()
+        .ok_or_else(|| Error::ParseError("missing field `start`".to_string()))?;
+
+    let (nodes, _) = load_config(config, path, &mut Vec::new())?;
+    Ok((start, nodes))
+}
+
+/// Builds the states of `config` and merges in its includes, along with the
+/// file that defined each state. `stack` holds the files currently being
+/// included, to detect cycles.
+fn load_config(
+    config: Config,
+    path: &Path,
+    stack: &mut Vec<(PathBuf, PathBuf)>,
+) -> Result<(HashMap<String, Node>, HashMap<String, PathBuf>)> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut nodes = states(config.state, dir)?;
+    let mut origins: HashMap<String, PathBuf> =
+        nodes.keys().map(|key| (key.clone(), path.to_path_buf())).collect();
+
+    stack.push((canonical(path), path.to_path_buf()));
+    for include in &config.include {
+        let file = dir.join(include);
+        if let Some(i) = stack.iter().position(|(p, _)| *p == canonical(&file)) {
+            let cycle = stack[i..]
+                .iter()
+                .map(|(_, p)| p.display().to_string())
+                .chain(std::iter::once(file.display().to_string()))
+                .collect::<Vec<_>>();
+            return Err(Error::IncludeError(format!(
+                "include cycle: {}",
+                cycle.join(" -> ")
+            )));
+        }
+
+        let content = std::fs::read_to_string(&file)
+            .map_err(|e| Error::FileError(format!("{}: {}", file.display(), e)))?;
+        let included: Config = toml::from_str(&content)
+            .map_err(|e| Error::ParseError(format!("{}: {}", file.display(), e)))?;
+        let namespace = file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let (included, included_origins) = load_config(included, &file, stack)?;
+        let included_origins: HashMap<_, _> = included_origins
+            .into_iter()
+            .map(|(key, origin)| (format!("{}.{}", namespace, key), origin))
+            .collect();
+        for (key, node) in namespaced(&namespace, included) {
+            let origin = &included_origins[&key];
+            if let Some(defined) = origins.get(&key) {
+                return Err(Error::IncludeError(format!(
+                    "state '{}' included from {} is already defined in {}",
+                    key,
+                    origin.display(),
+                    defined.display()
+                )));
+            }
+            origins.insert(key.clone(), origin.clone());
+            nodes.insert(key, node);
+        }
+    }
+    stack.pop();
+
+    Ok((nodes, origins))
+}
+
+pub(crate) fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
 
+/// Prefixes every state key with `namespace`, along with the transitions
+/// between them. Transitions to keys the included file does not define are
+/// left for the including file to resolve.
+fn namespaced(namespace: &str, nodes: HashMap<String, Node>) -> HashMap<String, Node> {
+    let rename = |key: &str| {
+        nodes
+            .contains_key(key)
+            .then(|| format!("{}.{}", namespace, key))
+    };
+    let rename_expr = |expr: &mut Expr| {
+        if let Expr::Value(Value::String(key) | Value::Symbol(key)) = expr {
+            if let Some(key) = rename(key) {
+                *expr = Expr::Value(Value::String(key));
+            }
+        }
+    };
+
+    let mut renamed = HashMap::new();
+    for (key, node) in &nodes {
+        let mut node = node.clone();
+        for target in node.state.targets_mut() {
+            match target {
+                Target::Key(to) => {
+                    if let Some(key) = rename(to) {
+                        *to = key;
+                    }
+                }
+                Target::Expr(expr) => rename_expr(expr),
+            }
+        }
+        renamed.insert(format!("{}.{}", namespace, key), node);
+    }
+    renamed
+}
+
+fn states(states: HashMap<String, StateConfig>, dir: &Path) -> Result<HashMap<String, Node>> {
     let mut nodes = HashMap::new();
-    for (key, state_config) in config.state {
+    for (key, state_config) in states {
         let (state, name) = match state_config {
-            StateConfig::Text { name, message, to } => (
-                State::Prompt(PromptType::Text(TextPrompt { message }), to),
+            StateConfig::Text {
                 name,
-            ),
-            StateConfig::Confirm { name, message, to } => (
-                State::Prompt(PromptType::Confirm(ConfirmPrompt { message }), to),
+                message,
+                default,
+                when,
+                validate,
+                error_message,
+                to,
+            } => {
+                let message = template(&key, &message)?;
+                let default = default.as_deref().map(parse).transpose()?;
+                let when = when.as_deref().map(parse).transpose()?;
+                let validation = validation(&key, validate, error_message)?;
+                (
+                    State::Prompt(
+                        PromptType::Text(TextPrompt {
+                            message,
+                            default,
+                            when,
+                            validation,
+                        }),
+                        to,
+                    ),
+                    name,
+                )
+            }
+            StateConfig::Confirm {
                 name,
-            ),
-            StateConfig::Password { name, message, to } => (
-                State::Prompt(PromptType::Password(PasswordPrompt { message }), to),
+                message,
+                default,
+                when,
+                to,
+            } => {
+                let message = template(&key, &message)?;
+                let default = default.as_deref().map(parse).transpose()?;
+                let when = when.as_deref().map(parse).transpose()?;
+                (
+                    State::Prompt(PromptType::Confirm(ConfirmPrompt {
+                            message,
+                            default,
+                            when,
+                        }), to),
+                    name,
+                )
+            }
+            StateConfig::Password {
                 name,
-            ),
+                message,
+                default,
+                when,
+                validate,
+                error_message,
+                to,
+            } => {
+                let message = template(&key, &message)?;
+                let default = default.as_deref().map(parse).transpose()?;
+                let when = when.as_deref().map(parse).transpose()?;
+                let validation = validation(&key, validate, error_message)?;
+                (
+                    State::Prompt(
+                        PromptType::Password(PasswordPrompt {
+                            message,
+                            default,
+                            when,
+                            validation,
+                        }),
+                        to,
+                    ),
+                    name,
+                )
+            }
+            StateConfig::Number {
+                name,
+                message,
+                default,
+                when,
+                min,
+                max,
+                step,
+                to,
+            } => {
+                let message = template(&key, &message)?;
+                let default = default.as_deref().map(parse).transpose()?;
+                let when = when.as_deref().map(parse).transpose()?;
+                let bounds = bounds(min, max, step)?;
+                (
+                    State::Prompt(
+                        PromptType::Number(NumberPrompt {
+                            message,
+                            default,
+                            when,
+                            bounds,
+                        }),
+                        to,
+                    ),
+                    name,
+                )
+            }
+            StateConfig::Integer {
+                name,
+                message,
+                default,
+                when,
+                min,
+                max,
+                step,
+                to,
+            } => {
+                let message = template(&key, &message)?;
+                let default = default.as_deref().map(parse).transpose()?;
+                let when = when.as_deref().map(parse).transpose()?;
+                let bounds = bounds(min, max, step)?;
+                (
+                    State::Prompt(
+                        PromptType::Integer(IntegerPrompt {
+                            message,
+                            default,
+                            when,
+                            bounds,
+                        }),
+                        to,
+                    ),
+                    name,
+                )
+            }
             StateConfig::Select {
                 name,
                 message,
                 options,
+                options_from,
+                label,
+                default,
+                when,
                 to,
             } => {
-                let options = options
-                    .iter()
-                    .map(|s| parse(s))
-                    .collect::<Result<Vec<_>>>()?;
+                let message = template(&key, &message)?;
+                let options = choices(&key, &options, options_from, label)?;
+                let default = default.as_deref().map(parse).transpose()?;
+                let when = when.as_deref().map(parse).transpose()?;
 
                 (
-                    State::Prompt(PromptType::Select(SelectPrompt { message, options }), to),
+                    State::Prompt(
+                        PromptType::Select(SelectPrompt {
+                            message,
+                            options,
+                            default,
+                            when,
+                        }),
+                        to,
+                    ),
                     name,
                 )
             }
@@ -120,16 +491,25 @@ pub fn load(content: &str) -> Result<(String, HashMap<String, Node>)> {
                 name,
                 message,
                 options,
+                options_from,
+                label,
+                default,
+                when,
                 to,
             } => {
-                let options = options
-                    .iter()
-                    .map(|s| parse(s))
-                    .collect::<Result<Vec<_>>>()?;
+                let message = template(&key, &message)?;
+                let options = choices(&key, &options, options_from, label)?;
+                let default = default.as_deref().map(parse).transpose()?;
+                let when = when.as_deref().map(parse).transpose()?;
 
                 (
                     State::Prompt(
-                        PromptType::MultiSelect(MultiSelectPrompt { message, options }),
+                        PromptType::MultiSelect(MultiSelectPrompt {
+                            message,
+                            options,
+                            default,
+                            when,
+                        }),
                         to,
                     ),
                     name,
@@ -139,16 +519,25 @@ pub fn load(content: &str) -> Result<(String, HashMap<String, Node>)> {
                 name,
                 message,
                 options,
+                options_from,
+                label,
+                default,
+                when,
                 to,
             } => {
-                let options = options
-                    .iter()
-                    .map(|s| parse(s))
-                    .collect::<Result<Vec<_>>>()?;
+                let message = template(&key, &message)?;
+                let options = choices(&key, &options, options_from, label)?;
+                let default = default.as_deref().map(parse).transpose()?;
+                let when = when.as_deref().map(parse).transpose()?;
 
                 (
                     State::Prompt(
-                        PromptType::FuzzySelect(FuzzySelectPrompt { message, options }),
+                        PromptType::FuzzySelect(FuzzySelectPrompt {
+                            message,
+                            options,
+                            default,
+                            when,
+                        }),
                         to,
                     ),
                     name,
@@ -188,6 +577,13 @@ pub fn load(content: &str) -> Result<(String, HashMap<String, Node>)> {
                     key.clone(),
                 )
             }
+            StateConfig::Switch { cases, default } => {
+                let cases = cases
+                    .into_iter()
+                    .map(|case| Ok((parse(&case.when)?, case.to)))
+                    .collect::<Result<_>>()?;
+                (State::Switch(Switch { cases, default }), key.clone())
+            }
             StateConfig::Goto { target } => (
                 State::Goto(Goto::new(parse(&target)?)),
                 key.clone(),
@@ -199,18 +595,227 @@ pub fn load(content: &str) -> Result<(String, HashMap<String, Node>)> {
             StateConfig::Remove { name, to } => {
                 (State::Remove(to), name.clone())
             }
+            StateConfig::Call {
+                name,
+                wizard,
+                args,
+                returns,
+                to,
+            } => {
+                let args = args
+                    .iter()
+                    .map(|(k, v)| Ok((k.clone(), parse(v)?)))
+                    .collect::<Result<_>>()?;
+
+                (
+                    State::Call(
+                        Call {
+                            wizard: dir.join(wizard),
+                            args,
+                            returns,
+                        },
+                        to,
+                    ),
+                    name,
+                )
+            }
+            StateConfig::Exec {
+                name,
+                command,
+                cwd,
+                env,
+                timeout,
+                output,
+                on_success,
+                on_failure,
+            } => {
+                let command = command
+                    .iter()
+                    .map(|arg| template(&key, arg))
+                    .collect::<Result<_>>()?;
+                let cwd = cwd.map(|cwd| template(&key, &cwd)).transpose()?;
+                let env = env
+                    .iter()
+                    .map(|(k, v)| Ok((k.clone(), template(&key, v)?)))
+                    .collect::<Result<_>>()?;
+
+                (
+                    State::Exec(Exec {
+                        command,
+                        cwd,
+                        env,
+                        timeout: timeout.map(Duration::from_secs),
+                        output,
+                        on_success,
+                        on_failure,
+                    }),
+                    name,
+                )
+            }
+            StateConfig::Review { message, to } => {
+                let message = template(&key, message.as_deref().unwrap_or("Review your answers"))?;
+                (State::Review(Review { message }, to), key.clone())
+            }
+            StateConfig::Collect {
+                name,
+                body,
+                item,
+                max,
+                confirm,
+                to,
+            } => {
+                if item.as_ref() == Some(&name) {
+                    return Err(Error::ParseError(format!(
+                        "state '{}': item must not be the collected variable '{}'",
+                        key, name
+                    )));
+                }
+                let max = max.as_deref().map(parse).transpose()?;
+                let confirm = confirm.map(|confirm| template(&key, &confirm)).transpose()?;
+
+                (
+                    State::Collect(Collect {
+                        body,
+                        item,
+                        max,
+                        confirm,
+                        to,
+                    }),
+                    name,
+                )
+            }
+            StateConfig::Foreach {
+                name,
+                over,
+                item,
+                index,
+                body,
+                collect,
+                keyed,
+                to,
+            } => (
+                State::Foreach(Foreach {
+                    over: parse(&over)?,
+                    item: item.unwrap_or_else(|| "item".to_string()),
+                    index: index.unwrap_or_else(|| "index".to_string()),
+                    body,
+                    collect,
+                    keyed,
+                    to,
+                }),
+                name,
+            ),
             StateConfig::Done => (State::Done, key.clone()),
         };
 
         nodes.insert(key.clone(), Node { name, state });
     }
 
-    Ok((config.start, nodes))
+    Ok(nodes)
+}
+
+fn template(key: &str, source: &str) -> Result<Template> {
+    Template::parse(source).map_err(|e| match e {
+        Error::InvalidTemplate(reason) => {
+            Error::InvalidTemplate(format!("in state '{}': {}", key, reason))
+        }
+        e => e,
+    })
+}
+
+fn bounds(min: Option<String>, max: Option<String>, step: Option<String>) -> Result<Bounds> {
+    Ok(Bounds {
+        min: min.as_deref().map(parse).transpose()?,
+        max: max.as_deref().map(parse).transpose()?,
+        step: step.as_deref().map(parse).transpose()?,
+    })
+}
+
+fn validation(
+    key: &str,
+    validate: Option<String>,
+    error_message: Option<String>,
+) -> Result<Option<Validation>> {
+    match (validate, error_message) {
+        (Some(validate), error_message) => Ok(Some(Validation {
+            expr: parse(&validate)?,
+            error_message: error_message.map(|m| template(key, &m)).transpose()?,
+        })),
+        (None, Some(_)) => Err(Error::ParseError(format!(
+            "State '{}' has an error_message without validate",
+            key
+        ))),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Options are expressions; one that does not parse as an expression but
+/// contains `${` is a label.
+/// Options generated by `options_from` come after them.
+fn choices(
+    key: &str,
+    options: &[OptionConfig],
+    options_from: Option<String>,
+    label: Option<String>,
+) -> Result<Vec<Choice>> {
+    let from = match (options_from, label) {
+        (Some(items), label) => Some(Choice::From {
+            items: parse(&items)?,
+            label: label.as_deref().map(parse).transpose()?,
+        }),
+        (None, Some(_)) => {
+            return Err(Error::ParseError(format!(
+                "State '{}' has a label without options_from",
+                key
+            )))
+        }
+        (None, None) if options.is_empty() => {
+            return Err(Error::ParseError(format!(
+                "State '{}' has neither options nor options_from",
+                key
+            )))
+        }
+        (None, None) => None,
+    };
+
+    options
+        .iter()
+        .map(|option| match option {
+            OptionConfig::Expr(option) => match parse(option) {
+                Ok(expr) => Ok(Choice::Expr(expr)),
+                Err(_) if option.contains("${") => Ok(Choice::Label(template(key, option)?)),
+                Err(e) => Err(e),
+            },
+            OptionConfig::Item {
+                label,
+                value,
+                description,
+            } => Ok(Choice::Item {
+                label: template(key, label)?,
+                value: value.as_deref().map(parse).transpose()?,
+                description: description.as_deref().map(|d| template(key, d)).transpose()?,
+            }),
+        })
+        .chain(from.map(Ok))
+        .collect()
+}
+
+/// Converts a TOML value into an expression value.
+pub fn to_value(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::Number(i as f64),
+        toml::Value::Float(f) => Value::Number(f),
+        toml::Value::Boolean(b) => Value::Boolean(b),
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(a) => Value::Array(a.into_iter().map(to_value).collect()),
+        toml::Value::Table(t) => Value::Object(t.into_iter().map(|(k, v)| (k, to_value(v))).collect()),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::parser::{Expr, Value};
+    use crate::parser::Expr;
 
     use super::*;
 
@@ -290,4 +895,199 @@ mod tests {
         let end_node = nodes.get("end").unwrap();
         assert!(matches!(end_node.state, State::Done));
     }
+
+    #[test]
+    fn test_load_malformed_template() {
+        let content = r#"
+            start = "greet"
+
+            [state.greet]
+            type = "text"
+            name = "greeting"
+            message = "Hello, ${$name"
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+
+        assert_eq!(
+            load(content).unwrap_err(),
+            Error::InvalidTemplate(
+                "in state 'greet': \"Hello, ${$name\": unterminated placeholder '${$name'"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_load_option_labels() {
+        let content = r#"
+            start = "deploy"
+
+            [state.deploy]
+            type = "select"
+            name = "deploy"
+            message = "Deploy?"
+            options = ["Deploy to ${$args.env}", "'Price ${x}'", "cancel"]
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+        let (_, nodes) = load(content).unwrap();
+
+        let State::Prompt(PromptType::Select(select), _) = &nodes["deploy"].state else {
+            panic!("Expected select");
+        };
+        assert!(matches!(&select.options[0], Choice::Label(_)));
+        assert_eq!(
+            select.options[1],
+            Choice::Expr(Expr::Value(Value::String("Price ${x}".to_string())))
+        );
+        assert_eq!(
+            select.options[2],
+            Choice::Expr(Expr::Value(Value::Symbol("cancel".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_load_without_options() {
+        let content = r#"
+            start = "color"
+
+            [state.color]
+            type = "multi_select"
+            name = "color"
+            message = "Colors?"
+            to = "end"
+
+            [state.end]
+            type = "done"
+        "#;
+
+        assert_eq!(
+            load(content).unwrap_err(),
+            Error::ParseError("State 'color' has neither options nor options_from".to_string())
+        );
+    }
+
+    fn write_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("promptoml-{}-{}", name, std::process::id()));
+        for (file, content) in files {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_load_include() {
+        let dir = write_files(
+            "include",
+            &[(
+                "common/auth.toml",
+                r#"
+                    [state.pick_profile]
+                    type = "select"
+                    name = "profile"
+                    message = "Profile?"
+                    options = ["'default'", "'prod'"]
+                    to = "check"
+
+                    [state.check]
+                    type = "condition"
+                    condition = "$profile == 'prod'"
+                    branches = { true = "pick_profile", false = "deploy" }
+                "#,
+            )],
+        );
+        let content = r#"
+            start = "auth.pick_profile"
+            include = ["common/auth.toml"]
+
+            [state.deploy]
+            type = "done"
+        "#;
+
+        let (start, nodes) = load_from(content, &dir.join("wizard.toml")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(start, "auth.pick_profile");
+        let mut keys: Vec<_> = nodes.keys().map(String::as_str).collect();
+        keys.sort();
+        assert_eq!(keys, vec!["auth.check", "auth.pick_profile", "deploy"]);
+
+        match &nodes["auth.pick_profile"].state {
+            State::Prompt(_, to) => assert_eq!(to, "auth.check"),
+            _ => panic!("Expected select prompt"),
+        }
+        match &nodes["auth.check"].state {
+            State::Condition(condition) => {
+                assert_eq!(
+                    condition.branches["true"],
+                    Expr::Value(Value::String("auth.pick_profile".to_string()))
+                );
+                assert_eq!(
+                    condition.branches["false"],
+                    Expr::Value(Value::Symbol("deploy".to_string()))
+                );
+            }
+            _ => panic!("Expected condition"),
+        }
+    }
+
+    #[test]
+    fn test_load_include_errors() {
+        let dir = write_files(
+            "include-errors",
+            &[
+                ("a.toml", "include = [\"b.toml\"]"),
+                ("b.toml", "include = [\"a.toml\"]"),
+                ("auth.toml", "[state.end]\ntype = \"done\""),
+                ("other/auth.toml", "[state.end]\ntype = \"done\""),
+            ],
+        );
+
+        let cycle = load_from(
+            "start = \"end\"\ninclude = [\"a.toml\"]",
+            &dir.join("wizard.toml"),
+        );
+        let duplicate = load_from(
+            "start = \"end\"\ninclude = [\"auth.toml\", \"other/auth.toml\"]",
+            &dir.join("wizard.toml"),
+        );
+        let defined = load_from(
+            "start = \"auth.end\"\ninclude = [\"auth.toml\"]\n[state.\"auth.end\"]\ntype = \"done\"",
+            &dir.join("wizard.toml"),
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            cycle.unwrap_err(),
+            Error::IncludeError(format!(
+                "include cycle: {} -> {} -> {}",
+                dir.join("a.toml").display(),
+                dir.join("b.toml").display(),
+                dir.join("a.toml").display()
+            ))
+        );
+        assert_eq!(
+            duplicate.unwrap_err(),
+            Error::IncludeError(format!(
+                "state 'auth.end' included from {} is already defined in {}",
+                dir.join("other/auth.toml").display(),
+                dir.join("auth.toml").display()
+            ))
+        );
+        assert_eq!(
+            defined.unwrap_err(),
+            Error::IncludeError(format!(
+                "state 'auth.end' included from {} is already defined in {}",
+                dir.join("auth.toml").display(),
+                dir.join("wizard.toml").display()
+            ))
+        );
+    }
 }
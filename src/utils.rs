@@ -1,9 +1,6 @@
-use crate::{
-    eval::Context,
-    parser::Value,
-};
+use crate::parser::Value;
 
-pub fn get_options(options: Vec<Value>, context: &mut Context) -> Vec<String> {
+pub fn get_options(options: Vec<Value>) -> Vec<String> {
     let mut opts = Vec::new();
     for option in options {
         match option {
@@ -11,12 +8,13 @@ pub fn get_options(options: Vec<Value>, context: &mut Context) -> Vec<String> {
             Value::Number(n) => opts.push(n.to_string()),
             Value::Boolean(b) => opts.push(b.to_string()),
             Value::Symbol(s) => opts.push(s),
+            Value::Null => {}
             Value::Array(a) => {
-                opts.extend(get_options(a, context));
+                opts.extend(get_options(a));
             }
             Value::Object(o) => {
-                let value = o.values().into_iter().map(|v| v.clone()).collect();
-                opts.extend(get_options(value, context));
+                let value = o.values().cloned().collect();
+                opts.extend(get_options(value));
             }
         }
     }